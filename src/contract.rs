@@ -1,15 +1,17 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
-use cw2::set_contract_version;
+use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult};
+use cw2::{get_contract_version, set_contract_version};
+use semver::Version;
 // use cw2::set_contract_version;
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 use crate::instantiate::instantiate as contract_instance;
 use crate::execute::execute as contract_executions;
 use crate::query::query as contract_queries;
+use crate::migrate::migrate as contract_migration;
 
 
 // version info for migration info
@@ -52,55 +54,175 @@ pub fn query(
     contract_queries(deps, env, msg)
 }
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Only upgrade this contract, and never to an older version
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidContractName {
+            expected: CONTRACT_NAME.to_string(),
+            found: stored.contract,
+        });
+    }
+    let parse = |version: &str| Version::parse(version).map_err(|err| StdError::generic_err(err.to_string()));
+    let from: Version = parse(&stored.version)?;
+    let to: Version = parse(CONTRACT_VERSION)?;
+    if from > to {
+        return Err(ContractError::CannotMigrateDowngrade {
+            from: stored.version,
+            to: CONTRACT_VERSION.to_string(),
+        });
+    }
+
+    let response = contract_migration(deps.branch(), env, msg, &from)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(response
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 // Contract test will go here and will be update
 #[cfg(test)]
 mod tests {
 
     use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, message_info};
-    use cosmwasm_std::{attr, coins, from_json, Addr, Uint128};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, message_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::{
+        attr, coin, coins, from_json, to_json_binary, Addr, BankMsg, Binary, CosmosMsg, Decimal256,
+        Int128, OwnedDeps, Uint128, WasmMsg,
+    };
+    use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 
-    use crate::msg::{self, ExecuteMsg, InstantiateMsg};
-    use crate::state::CONFIG;
+    use crate::msg::{self, ExecuteMsg, InstantiateMsg, QueryMsg};
+    use crate::oracle::{signed_price_message, PriceSignature, SignedPrice};
+    use crate::state::{OracleSource, SignatureScheme, StakeAsset, CONFIG, LEADERBOARDENTRY};
     
     fn inst(deps: DepsMut, addr: &Addr ) {
+        inst_with_fee(deps, addr, 200);
+    }
+
+    fn inst_with_fee(deps: DepsMut, addr: &Addr, treasury_fee: u64) {
         let msg = InstantiateMsg {
-            treasury_fee: 200
+            stake_asset: StakeAsset::Native { denom: "uzig".to_string() },
+            oracle: OracleSource::Manual,
+            treasury_fee,
+            operator: None,
+            pauser: None,
+            treasury: None,
+            keeper_reward: 0,
+            min_bet: Uint128::zero(),
+            max_bet: None,
+            max_user_stake: None,
+            max_round_pool: None,
+            cancel_fee: 0,
+            betting_interval: 300,
+            live_interval: 300,
+            buffer_seconds: 30,
         };
         let env = mock_env();
-        let info = message_info(&addr, &[]);
+        let info = message_info(addr, &[]);
 
         instantiate(deps, env, info, msg).unwrap();
     }
 
+    fn bet(deps: DepsMut, env: &Env, user: &Addr, round_id: u64, direction: msg::Direction, amount: u128) {
+        let info = message_info(user, &coins(amount, "uzig"));
+        let msg = ExecuteMsg::PlaceBet { round_id, direction };
+
+        execute(deps, env.clone(), info, msg).unwrap();
+    }
+
+    // Alice wins round 1 (20 vs 10) and round 2 (10 vs 10) against bob
+    fn run_two_rounds(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, admin: &Addr) -> Env {
+        let admin = message_info(admin, &[]);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 20);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 10);
+
+        env.block.time = start.plus_seconds(300);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        bet(deps.as_mut(), &env, &alice, 2, msg::Direction::Up, 10);
+        bet(deps.as_mut(), &env, &bob, 2, msg::Direction::Down, 10);
+
+        env.block.time = start.plus_seconds(600);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg).unwrap();
+
+        env.block.time = start.plus_seconds(900);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(120u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin, exec_msg).unwrap();
+
+        env
+    }
+
     #[test]
     fn test_instantiate_contract () {
         let mut deps = mock_dependencies();
         let addr = deps.api.addr_make("creator");
 
         let instantiate_msg: InstantiateMsg = InstantiateMsg {
-            treasury_fee: 500
+            stake_asset: StakeAsset::Native { denom: "uzig".to_string() },
+            oracle: OracleSource::Manual,
+            treasury_fee: 500,
+            operator: None,
+            pauser: None,
+            treasury: None,
+            keeper_reward: 0,
+            min_bet: Uint128::zero(),
+            max_bet: None,
+            max_user_stake: None,
+            max_round_pool: None,
+            cancel_fee: 0,
+            betting_interval: 300,
+            live_interval: 600,
+            buffer_seconds: 30,
         };
         
         let info = message_info(&addr, &[]);
         let env = mock_env();
 
+        // The lock and end windows of one ExecuteRound would never overlap
+        instantiate(deps.as_mut(), env.clone(), info.clone(), instantiate_msg.clone()).unwrap_err();
+
+        let instantiate_msg = InstantiateMsg { live_interval: 320, ..instantiate_msg };
         let response = instantiate(deps.as_mut(), env, info.clone(), instantiate_msg).unwrap();
 
         assert_eq!(
             response.attributes,
             vec![
                 attr("action", "instantiate"),
-                attr("admin", info.sender.to_string()),
-                attr("treasury-fee", "500")
+                attr("owner", info.sender.to_string()),
+                attr("operator", info.sender.to_string()),
+                attr("pauser", info.sender.to_string()),
+                attr("treasury", info.sender.to_string()),
+                attr("stake-asset", "uzig"),
+                attr("treasury-fee", "500"),
+                attr("keeper-reward", "0"),
+                attr("betting-interval", "300"),
+                attr("live-interval", "320"),
+                attr("buffer-seconds", "30")
             ]
         );
 
         let config = CONFIG.load(&deps.storage).unwrap();
-        assert_eq!(config.admin, info.sender);
+        let roles = crate::state::ROLES.load(&deps.storage).unwrap();
+        assert_eq!(roles.owner, info.sender);
+        assert_eq!(config.stake_asset, StakeAsset::Native { denom: "uzig".to_string() });
         assert_eq!(config.treasury_fee, 500);
-        assert_eq!(config.paused, false);
-        assert_eq!(config.current_round_id, 1);
+        assert!(!config.paused);
+        assert_eq!(config.current_round_id, 0);
+        assert_eq!(config.betting_interval, 300);
+        assert_eq!(config.live_interval, 320);
+        assert_eq!(config.buffer_seconds, 30);
     }
 
     #[test]
@@ -118,7 +240,7 @@ mod tests {
         // let config = CONFIG.load(deps.storage).unwrap();
 
         let msg = ExecuteMsg::PlaceBet { round_id: 1, direction: msg::Direction::Down };
-        let start_msg = ExecuteMsg::StartRound { price: Uint128::from(123456u64) };
+        let start_msg = ExecuteMsg::GenesisStartRound {};
 
         // call the contract
         execute(deps.as_mut(), env.clone(), into.clone(), start_msg).unwrap();
@@ -132,9 +254,1473 @@ mod tests {
                 attr("round_id", "1"),
                 attr("user", info.sender.to_string()),
                 attr("amount", "20"),
-                attr("direction", "Down")
+                attr("direction", "Down"),
+                attr("position", "20")
+            ]
+        )
+    }
+
+    #[test]
+    fn test_bet_top_ups() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 20);
+        let top_up = |direction| ExecuteMsg::PlaceBet { round_id: 1, direction };
+        let info = message_info(&alice, &coins(15, "uzig"));
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), top_up(msg::Direction::Up)).unwrap();
+        assert_eq!(res.attributes[5], attr("position", "35"));
+
+        // Hedging on the other side is not supported
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), top_up(msg::Direction::Down)).unwrap_err();
+        assert!(matches!(err, ContractError::BetDirectionMismatch {}));
+
+        let round = crate::state::ROUNDS.load(&deps.storage, 1).unwrap();
+        assert_eq!((round.bull_pool, round.total_pool), (Uint128::from(35u64), Uint128::from(35u64)));
+        assert_eq!(round.participant_count, 1);
+        let board = crate::state::LEADERBOARDENTRY.load(&deps.storage, &alice).unwrap();
+        assert_eq!((board.total_amount_played, board.total_up), (Uint128::from(35u64), 1));
+
+        // The cap covers the whole position
+        let update = msg::UpdateConfigMsg { max_user_stake: Some(Uint128::from(40u64)), ..Default::default() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::UpdateConfig(update)).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), info.clone(), top_up(msg::Direction::Up)).unwrap_err();
+        assert!(matches!(err, ContractError::UserStakeCapExceeded { cap } if cap == Uint128::from(40u64)));
+        let update = msg::UpdateConfigMsg { max_user_stake: Some(Uint128::zero()), ..Default::default() };
+        execute(deps.as_mut(), env.clone(), admin, ExecuteMsg::UpdateConfig(update)).unwrap();
+        execute(deps.as_mut(), env.clone(), info, top_up(msg::Direction::Up)).unwrap();
+        let bet = crate::state::USER_BETS.load(&deps.storage, (1, &alice)).unwrap();
+        assert_eq!(bet.amount, Uint128::from(50u64));
+    }
+
+    #[test]
+    fn test_bet_limits() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+
+        let update = msg::UpdateConfigMsg { min_bet: Some(Uint128::from(50u64)), max_bet: Some(Uint128::from(40u64)), ..Default::default() };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::UpdateConfig(update)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBetLimits {}));
+        let update = msg::UpdateConfigMsg { min_bet: Some(Uint128::from(50u64)), max_user_stake: Some(Uint128::from(40u64)), ..Default::default() };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::UpdateConfig(update)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidBetLimits {}));
+        let update = msg::UpdateConfigMsg {
+            min_bet: Some(Uint128::from(10u64)),
+            max_bet: Some(Uint128::from(100u64)),
+            max_user_stake: Some(Uint128::from(150u64)),
+            max_round_pool: Some(Uint128::from(200u64)),
+            ..Default::default()
+        };
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::UpdateConfig(update)).unwrap();
+        let config: crate::state::Config = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetConfig {}).unwrap()).unwrap();
+        assert_eq!((config.min_bet, config.max_bet), (Uint128::from(10u64), Some(Uint128::from(100u64))));
+        assert_eq!(config.max_round_pool, Some(Uint128::from(200u64)));
+
+        let place = |deps: DepsMut, user: &Addr, amount: u128| {
+            let info = message_info(user, &coins(amount, "uzig"));
+            execute(deps, env.clone(), info, ExecuteMsg::PlaceBet { round_id: 1, direction: msg::Direction::Up })
+        };
+        let err = place(deps.as_mut(), &alice, 9).unwrap_err();
+        assert!(matches!(err, ContractError::BetBelowMinimum { min } if min == Uint128::from(10u64)));
+        let err = place(deps.as_mut(), &alice, 101).unwrap_err();
+        assert!(matches!(err, ContractError::BetAboveMaximum { max } if max == Uint128::from(100u64)));
+        place(deps.as_mut(), &alice, 100).unwrap();
+        let err = place(deps.as_mut(), &alice, 60).unwrap_err();
+        assert!(matches!(err, ContractError::UserStakeCapExceeded { cap } if cap == Uint128::from(150u64)));
+        place(deps.as_mut(), &alice, 50).unwrap();
+        let err = place(deps.as_mut(), &bob, 60).unwrap_err();
+        assert!(matches!(err, ContractError::RoundPoolCapExceeded { cap } if cap == Uint128::from(200u64)));
+        place(deps.as_mut(), &bob, 50).unwrap();
+    }
+
+    #[test]
+    fn test_zero_limits_at_instantiate() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        let msg = InstantiateMsg {
+            stake_asset: StakeAsset::Native { denom: "uzig".to_string() },
+            oracle: OracleSource::Manual,
+            treasury_fee: 200,
+            operator: None,
+            pauser: None,
+            treasury: None,
+            keeper_reward: 0,
+            min_bet: Uint128::from(10u64),
+            max_bet: Some(Uint128::zero()),
+            max_user_stake: Some(Uint128::zero()),
+            max_round_pool: Some(Uint128::from(5u64)),
+            cancel_fee: 0,
+            betting_interval: 300,
+            live_interval: 300,
+            buffer_seconds: 30,
+        };
+
+        // The round pool cap is below the minimum bet
+        instantiate(deps.as_mut(), mock_env(), message_info(&addr, &[]), msg.clone()).unwrap_err();
+
+        // Zero means no limit, as in UpdateConfig
+        let msg = InstantiateMsg { max_round_pool: Some(Uint128::zero()), ..msg };
+        instantiate(deps.as_mut(), mock_env(), message_info(&addr, &[]), msg).unwrap();
+        let config: crate::state::Config = from_json(query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap()).unwrap();
+        assert_eq!((config.max_bet, config.max_user_stake, config.max_round_pool), (None, None, None));
+        execute(deps.as_mut(), mock_env(), message_info(&addr, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &mock_env(), &addr, 1, msg::Direction::Up, 1_000);
+    }
+
+    #[test]
+    fn test_odds_simulated_payout_and_dust() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        let mut env = mock_env();
+        let start = env.block.time;
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 100);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 50);
+
+        // 147 of the 150 pool is left after the 2% fee
+        let odds: msg::OddsResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetOdds { round_id: 1 }).unwrap()).unwrap();
+        assert_eq!(odds.bull_multiplier, Some(Decimal256::percent(147)));
+        assert_eq!(odds.bear_multiplier, Some(Decimal256::percent(294)));
+
+        let simulate = QueryMsg::SimulateBet { round_id: 1, direction: msg::Direction::Up, amount: Uint128::from(50u64) };
+        let simulated: msg::SimulateBetResponse = from_json(query(deps.as_ref(), env.clone(), simulate.clone()).unwrap()).unwrap();
+        assert_eq!(simulated.payout, Uint128::from(65u64));
+
+        // The claim pays exactly the simulated amount
+        bet(deps.as_mut(), &env, &carol, 1, msg::Direction::Up, 50);
+        env.block.time = start.plus_seconds(300);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        assert!(query(deps.as_ref(), env.clone(), simulate).is_err());
+        env.block.time = start.plus_seconds(600);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin, exec_msg).unwrap();
+        let res = execute(deps.as_mut(), env.clone(), message_info(&carol, &[]), ExecuteMsg::ClaimReward { round_id: 1 }).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: carol.to_string(), amount: coins(65, "uzig") }));
+
+        // Alice gets 130 of her 130.67, the rounding dust goes to the treasury with the
+        // 4 fee once the last bet is settled
+        let treasury: msg::TreasuryResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetTreasury {}).unwrap()).unwrap();
+        assert_eq!(treasury.accrued, Uint128::from(4u64));
+        execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), ExecuteMsg::ProcessSettlement { round_id: 1, limit: 10 }).unwrap();
+        let winnings: Uint128 = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetWinnings { user: alice.to_string() }).unwrap()).unwrap();
+        assert_eq!(winnings, Uint128::from(130u64));
+        let treasury: msg::TreasuryResponse = from_json(query(deps.as_ref(), env, QueryMsg::GetTreasury {}).unwrap()).unwrap();
+        assert_eq!(treasury.accrued, Uint128::from(5u64));
+    }
+
+    #[test]
+    fn test_cancel_bet() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let mut env = mock_env();
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        let update = msg::UpdateConfigMsg { cancel_fee: Some(500), ..Default::default() };
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::UpdateConfig(update)).unwrap();
+        let update = msg::UpdateConfigMsg { cancel_fee: Some(10_001), ..Default::default() };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::UpdateConfig(update)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCancelFee {}));
+
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 100);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 50);
+
+        // 5% of the stake stays with the treasury
+        let cancel = ExecuteMsg::CancelBet { round_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), cancel.clone()).unwrap();
+        assert_eq!(res.attributes[3], attr("refund", "95"));
+        assert_eq!(res.attributes[4], attr("fee", "5"));
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: alice.to_string(), amount: coins(95, "uzig") }));
+        let treasury: msg::TreasuryResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetTreasury {}).unwrap()).unwrap();
+        assert_eq!(treasury.accrued, Uint128::from(5u64));
+
+        let round = crate::state::ROUNDS.load(&deps.storage, 1).unwrap();
+        assert_eq!((round.bull_pool, round.bear_pool, round.total_pool), (Uint128::zero(), Uint128::from(50u64), Uint128::from(50u64)));
+        assert_eq!(round.participant_count, 1);
+        let board = crate::state::LEADERBOARDENTRY.load(&deps.storage, &alice).unwrap();
+        assert_eq!((board.total_amount_played, board.total_up), (Uint128::zero(), 0));
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), cancel.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::BetNotFound {}));
+
+        // A cancelled bet can be placed again, until the round locks
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Down, 10);
+        assert_eq!(crate::state::ROUNDS.load(&deps.storage, 1).unwrap().participant_count, 2);
+        env.block.time = env.block.time.plus_seconds(300);
+        let err = execute(deps.as_mut(), env, message_info(&bob, &[]), cancel).unwrap_err();
+        assert!(matches!(err, ContractError::RoundLocked {}));
+    }
+
+    #[test]
+    fn test_round_participants() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), message_info(&addr, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+        let mut bettors: Vec<Addr> = (0..5).map(|i| deps.api.addr_make(&format!("bettor{i}"))).collect();
+        for bettor in bettors.iter() {
+            bet(deps.as_mut(), &env, bettor, 1, msg::Direction::Up, 10);
+        }
+        bettors.sort();
+
+        let round: msg::RoundResponse =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetRoundDetails { round_id: 1 }).unwrap()).unwrap();
+        assert_eq!(round.participant_count, 5);
+
+        // Bettors are paged in address order
+        let page = |start_after: Option<String>| -> msg::ParticipantsResponse {
+            let msg = QueryMsg::GetRoundParticipants { round_id: 1, start_after, limit: Some(3), order: None };
+            from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        let first = page(None);
+        assert_eq!(first.participants, bettors[..3]);
+        let second = page(first.next_key);
+        assert_eq!(second.participants, bettors[3..]);
+        assert_eq!(second.next_key, None);
+    }
+
+    #[test]
+    fn test_list_pagination() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let env = run_two_rounds(&mut deps, &addr);
+
+        // Latest rounds first, two per page
+        let rounds = |start_after: Option<u64>| -> msg::RoundsResponse {
+            let msg = QueryMsg::GetAllRoundDetails {
+                start_after,
+                limit: Some(2),
+                order: Some(msg::OrderBy::Descending),
+            };
+            from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        let first = rounds(None);
+        assert_eq!(first.rounds.iter().map(|round| round.id).collect::<Vec<_>>(), vec![4, 3]);
+        assert_eq!(first.next_key, Some(3));
+        let second = rounds(first.next_key);
+        assert_eq!(second.rounds.iter().map(|round| round.id).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(second.next_key, None);
+
+        // The limit is capped
+        let msg = QueryMsg::GetAllUserPredictions { start_after: None, limit: Some(1_000), order: None };
+        let predictions: msg::PredictionsResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(predictions.predictions.len(), 4);
+        assert_eq!(predictions.next_key, None);
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let sort_by = Some(msg::LeaderboardSort::Volume);
+        let msg = QueryMsg::GetLeaderboard { sort_by: sort_by.clone(), limit: Some(1), start_after: None };
+        let board: msg::LeaderboardResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        assert_eq!(board.entries[0].user, alice);
+        let msg = QueryMsg::GetLeaderboard { sort_by, limit: Some(1), start_after: board.next_key };
+        let board: msg::LeaderboardResponse = from_json(query(deps.as_ref(), env, msg).unwrap()).unwrap();
+        assert_eq!(board.entries[0].user, bob);
+        assert_eq!(board.next_key, None);
+    }
+
+    #[test]
+    fn test_leaderboard_ranking() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst_with_fee(deps.as_mut(), &addr, 0);
+        let env = run_two_rounds(&mut deps, &addr);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        bet(deps.as_mut(), &env, &carol, 4, msg::Direction::Up, 100);
+        for round_id in [1, 2] {
+            let msg = ExecuteMsg::ProcessSettlement { round_id, limit: 10 };
+            execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), msg).unwrap();
+        }
+
+        let leaderboard = |sort_by: msg::LeaderboardSort, start_after: Option<String>| -> msg::LeaderboardResponse {
+            let msg = QueryMsg::GetLeaderboard { sort_by: Some(sort_by), limit: Some(2), start_after };
+            from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        let users = |board: &msg::LeaderboardResponse| board.entries.iter().map(|entry| entry.user.clone()).collect::<Vec<_>>();
+
+        // Alice won both settled rounds, carol's bet is still running
+        let first = leaderboard(msg::LeaderboardSort::NetProfit, None);
+        assert_eq!(users(&first), vec![alice.clone(), carol.clone()]);
+        assert_eq!(first.entries[0].net_profit, Int128::new(20));
+        assert_eq!(first.next_key, Some(carol.to_string()));
+        let second = leaderboard(msg::LeaderboardSort::NetProfit, first.next_key);
+        assert_eq!(users(&second), vec![bob.clone()]);
+        assert_eq!(second.entries[0].net_profit, Int128::new(-20));
+        assert_eq!(second.next_key, None);
+
+        assert_eq!(users(&leaderboard(msg::LeaderboardSort::Volume, None)), vec![carol.clone(), alice.clone()]);
+        let by_wins = leaderboard(msg::LeaderboardSort::WinCount, None);
+        assert_eq!((by_wins.entries[0].user.clone(), by_wins.entries[0].total_won), (alice.clone(), 2));
+        let by_amount = leaderboard(msg::LeaderboardSort::AmountWon, None);
+        assert_eq!(by_amount.entries[0].amount_won, Uint128::from(50u64));
+
+        let rank = |user: &Addr, sort_by: Option<msg::LeaderboardSort>| -> msg::RankResponse {
+            let msg = QueryMsg::GetUserRank { user: user.to_string(), sort_by };
+            from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(rank(&bob, None).rank, Some(3));
+        assert_eq!(rank(&alice, None).rank, Some(1));
+        assert_eq!(rank(&carol, Some(msg::LeaderboardSort::Volume)).rank, Some(1));
+        assert_eq!(rank(&bob, Some(msg::LeaderboardSort::Volume)).rank, Some(3));
+        let stranger = rank(&deps.api.addr_make("stranger"), None);
+        assert_eq!((stranger.rank, stranger.entry), (None, None));
+    }
+
+    #[test]
+    fn test_user_rank_is_bounded() {
+        let mut deps = mock_dependencies();
+        let user = deps.api.addr_make("user");
+        LEADERBOARDENTRY.save(&mut deps.storage, &user, &msg::LeaderboardEntry::new(user.clone())).unwrap();
+        let rank = |deps: Deps| -> msg::RankResponse {
+            let msg = QueryMsg::GetUserRank { user: user.to_string(), sort_by: None };
+            from_json(query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+
+        // Only the first 1000 ranks are counted
+        let leader = |deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, i: u32| {
+            let addr = deps.api.addr_make(&format!("leader{i}"));
+            let mut entry = msg::LeaderboardEntry::new(addr.clone());
+            entry.net_profit = Int128::one();
+            LEADERBOARDENTRY.save(&mut deps.storage, &addr, &entry).unwrap();
+        };
+        (0..999).for_each(|i| leader(&mut deps, i));
+        assert_eq!(rank(deps.as_ref()).rank, Some(1000));
+        leader(&mut deps, 999);
+        let outside = rank(deps.as_ref());
+        assert_eq!(outside.rank, None);
+        assert!(outside.entry.is_some());
+    }
+
+    #[test]
+    fn test_user_portfolio() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst_with_fee(deps.as_mut(), &addr, 0);
+        let env = run_two_rounds(&mut deps, &addr);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        bet(deps.as_mut(), &env, &alice, 4, msg::Direction::Down, 5);
+
+        let portfolio = |deps: Deps, user: &Addr, start_after: Option<u64>| -> msg::PortfolioResponse {
+            let msg = QueryMsg::GetUserPortfolio { user: user.to_string(), start_after, limit: Some(2) };
+            from_json(query(deps, env.clone(), msg).unwrap()).unwrap()
+        };
+        let first = portfolio(deps.as_ref(), &alice, None);
+        assert_eq!(first.positions.iter().map(|position| position.round_id).collect::<Vec<_>>(), vec![4, 2]);
+        assert_eq!(first.next_key, Some(2));
+        assert_eq!(first.positions[0].status, msg::RoundStatus::Open);
+        assert_eq!(first.positions[0].payout, None);
+        assert_eq!(first.positions[1].result, Some(msg::Direction::Up));
+        assert_eq!(first.positions[1].payout, Some(Uint128::from(20u64)));
+        assert_eq!(first.pending_stake, Uint128::from(5u64));
+        assert_eq!(first.claimable, Uint128::from(20u64));
+
+        let second = portfolio(deps.as_ref(), &alice, first.next_key);
+        assert_eq!(second.positions.len(), 1);
+        assert_eq!(second.positions[0].payout, Some(Uint128::from(30u64)));
+        assert_eq!(second.claimable, Uint128::from(30u64));
+        assert_eq!(second.next_key, None);
+
+        // Claimed rewards are no longer claimable
+        let alice_info = message_info(&alice, &[]);
+        execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::ClaimReward { round_id: 1 }).unwrap();
+        let after_claim = portfolio(deps.as_ref(), &alice, first.next_key);
+        assert!(after_claim.positions[0].claimed);
+        assert_eq!(after_claim.claimable, Uint128::zero());
+
+        // Settled rewards move to the ledger and are not counted twice once withdrawn
+        execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::ProcessSettlement { round_id: 2, limit: 10 })
+            .unwrap();
+        let settled = portfolio(deps.as_ref(), &alice, None);
+        assert_eq!((settled.claimable, settled.winnings), (Uint128::zero(), Uint128::from(20u64)));
+        execute(deps.as_mut(), env.clone(), alice_info, ExecuteMsg::WithdrawWinnings {}).unwrap();
+        let withdrawn = portfolio(deps.as_ref(), &alice, None);
+        assert_eq!((withdrawn.claimable, withdrawn.winnings), (Uint128::zero(), Uint128::zero()));
+
+        // Lost bets pay nothing
+        let bob_portfolio = portfolio(deps.as_ref(), &bob, None);
+        assert_eq!(bob_portfolio.positions[1].payout, Some(Uint128::zero()));
+        assert_eq!(bob_portfolio.claimable, Uint128::zero());
+    }
+
+    #[test]
+    fn test_seasons() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        let create = |start_time: u64, payout_curve: Vec<u64>| ExecuteMsg::CreateSeason {
+            start_time,
+            end_time: start.plus_seconds(1_000).seconds(),
+            fee_share: 5_000,
+            payout_curve,
+        };
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), create(start.seconds() + 1, vec![10_000])).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOwner {}));
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), create(start.seconds(), vec![10_000])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSeasonTime {}));
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), create(start.seconds() + 1, vec![7_000, 4_000])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPayoutCurve {}));
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), create(start.seconds() + 1, vec![7_000, 3_000])).unwrap();
+        assert_eq!(res.attributes[1], attr("season_id", "1"));
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), create(start.seconds() + 500, vec![10_000])).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSeasonTime {}));
+
+        // Round 1 starts within the season
+        env.block.time = start.plus_seconds(1);
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 2_000);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 1_000);
+        bet(deps.as_mut(), &env, &carol, 1, msg::Direction::Down, 500);
+        let fund = ExecuteMsg::FundSeason { season_id: 1 };
+        execute(deps.as_mut(), env.clone(), message_info(&bob, &coins(65, "uzig")), fund.clone()).unwrap();
+
+        env.block.time = start.plus_seconds(301);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        // Round 2 only gets bets on one side, it is voided when it locks
+        bet(deps.as_mut(), &env, &carol, 2, msg::Direction::Up, 100);
+        env.block.time = start.plus_seconds(601);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg).unwrap();
+
+        // Half of the 70 fee goes to the prize pool
+        let season = |deps: Deps, env: &Env| -> msg::SeasonResponse {
+            from_json(query(deps, env.clone(), QueryMsg::GetSeason { season_id: 1 }).unwrap()).unwrap()
+        };
+        assert_eq!(season(deps.as_ref(), &env).prize_pool, Uint128::from(100u64));
+        let treasury: msg::TreasuryResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetTreasury {}).unwrap()).unwrap();
+        assert_eq!(treasury.accrued, Uint128::from(35u64));
+
+        // The season stays open until it is over and its bets are settled
+        let claim = ExecuteMsg::ClaimSeasonPrize { season_id: 1 };
+        env.block.time = start.plus_seconds(1_000);
+        let err = execute(deps.as_mut(), env.clone(), message_info(&bob, &coins(10, "uzig")), fund).unwrap_err();
+        assert!(matches!(err, ContractError::SeasonEnded {}));
+        assert_eq!(season(deps.as_ref(), &env).open_bets, 3);
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), claim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::SeasonNotClosed {}));
+        let process = ExecuteMsg::ProcessSettlement { round_id: 1, limit: 10 };
+        execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), process).unwrap();
+        assert!(season(deps.as_ref(), &env).closed);
+
+        let msg = QueryMsg::GetSeasonLeaderboard { season_id: 1, limit: None, start_after: None };
+        let board: msg::LeaderboardResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        let users: Vec<Addr> = board.entries.iter().map(|entry| entry.user.clone()).collect();
+        assert_eq!(users, vec![alice.clone(), carol.clone(), bob.clone()]);
+        assert_eq!(board.entries[0].net_profit, Int128::new(1_430));
+
+        // The top two share the pool 70/30
+        let res = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), claim.clone()).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: alice.to_string(), amount: coins(70, "uzig") }));
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), claim.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyClaimed {}));
+        let res = execute(deps.as_mut(), env.clone(), message_info(&carol, &[]), claim.clone()).unwrap();
+        assert_eq!(res.attributes[3], attr("rank", "2"));
+        assert_eq!(res.attributes[4], attr("prize", "30"));
+        let err = execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), claim).unwrap_err();
+        assert!(matches!(err, ContractError::NoSeasonPrize {}));
+    }
+
+    #[test]
+    fn test_season_sweep_and_ranking() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let carol = deps.api.addr_make("carol");
+        let dave = deps.api.addr_make("dave");
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        let create = ExecuteMsg::CreateSeason {
+            start_time: start.seconds() + 1,
+            end_time: start.plus_seconds(1_000).seconds(),
+            fee_share: 5_000,
+            payout_curve: vec![6_000, 3_000, 1_000],
+        };
+        execute(deps.as_mut(), env.clone(), admin.clone(), create).unwrap();
+
+        // Carol cancels her only bet and Dave's only round is voided, neither is ranked
+        env.block.time = start.plus_seconds(1);
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 2_000);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 1_000);
+        bet(deps.as_mut(), &env, &carol, 1, msg::Direction::Down, 500);
+        execute(deps.as_mut(), env.clone(), message_info(&carol, &[]), ExecuteMsg::CancelBet { round_id: 1 }).unwrap();
+        let fund = ExecuteMsg::FundSeason { season_id: 1 };
+        execute(deps.as_mut(), env.clone(), message_info(&bob, &coins(70, "uzig")), fund).unwrap();
+
+        env.block.time = start.plus_seconds(301);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        bet(deps.as_mut(), &env, &dave, 2, msg::Direction::Up, 100);
+        env.block.time = start.plus_seconds(601);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg).unwrap();
+
+        let sweep = ExecuteMsg::SweepSeason { season_id: 1 };
+        env.block.time = start.plus_seconds(1_000);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), sweep.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::SeasonNotClosed {}));
+        let process = ExecuteMsg::ProcessSettlement { round_id: 1, limit: 10 };
+        execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), process).unwrap();
+
+        let msg = QueryMsg::GetSeasonLeaderboard { season_id: 1, limit: None, start_after: None };
+        let board: msg::LeaderboardResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        let users: Vec<Addr> = board.entries.iter().map(|entry| entry.user.clone()).collect();
+        assert_eq!(users, vec![alice.clone(), bob.clone()]);
+
+        // Only two of the three ranks are reached, the last 10% of the 100 pool is swept
+        let err = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), sweep.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOwner {}));
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), sweep.clone()).unwrap();
+        assert_eq!(res.attributes[2], attr("amount", "10"));
+        let treasury: msg::TreasuryResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetTreasury {}).unwrap()).unwrap();
+        assert_eq!(treasury.accrued, Uint128::from(40u64));
+        let err = execute(deps.as_mut(), env.clone(), admin, sweep).unwrap_err();
+        assert!(matches!(err, ContractError::SeasonAlreadySwept {}));
+
+        let claim = ExecuteMsg::ClaimSeasonPrize { season_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), claim.clone()).unwrap();
+        assert_eq!(res.attributes[3], attr("rank", "2"));
+        assert_eq!(res.attributes[4], attr("prize", "30"));
+        for user in [&carol, &dave] {
+            let err = execute(deps.as_mut(), env.clone(), message_info(user, &[]), claim.clone()).unwrap_err();
+            assert!(matches!(err, ContractError::NoSeasonPrize {}));
+        }
+        let res = execute(deps.as_mut(), env, message_info(&alice, &[]), claim).unwrap();
+        assert_eq!(res.attributes[4], attr("prize", "60"));
+    }
+
+    #[test]
+    fn test_round_timing_windows() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let admin = message_info(&addr, &[]);
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+
+        let round = crate::state::ROUNDS.load(&deps.storage, 1).unwrap();
+        assert_eq!(round.lock_time, start.plus_seconds(300));
+        assert_eq!(round.end_time, start.plus_seconds(600));
+
+        // Locking before lock time is rejected
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::CannotLockBeforeTime {}));
+
+        // Locking after the buffer has passed is rejected
+        env.block.time = start.plus_seconds(331);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::LockWindowExpired {}));
+
+        env.block.time = start.plus_seconds(330);
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+
+        // The live interval starts when the round is actually locked
+        let round = crate::state::ROUNDS.load(&deps.storage, 1).unwrap();
+        assert_eq!(round.end_time, start.plus_seconds(630));
+
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(120u64)), signed_price: None };
+        // Round 2 locks when round 1 ends, so a late call misses both windows
+        env.block.time = start.plus_seconds(661);
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::LockWindowExpired {}));
+
+        env.block.time = start.plus_seconds(630);
+        execute(deps.as_mut(), env, admin, exec_msg).unwrap();
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let admin = message_info(&addr, &[]);
+        let alice = deps.api.addr_make("alice");
+        let mut env = mock_env();
+        let start = env.block.time;
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        env.block.time = start.plus_seconds(300);
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg.clone()).unwrap();
+
+        // A short pause leaves the pipeline running
+        env.block.time = start.plus_seconds(310);
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::Pause {}).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::ContractPaused {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::Resume {}).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap_err();
+        assert!(matches!(err, ContractError::GenesisAlreadyStarted {}));
+
+        env.block.time = start.plus_seconds(600);
+        execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg.clone()).unwrap();
+        bet(deps.as_mut(), &env, &alice, 3, msg::Direction::Up, 10);
+
+        // A long pause misses the windows of the running rounds, a genesis restart voids
+        // them and opens a new round
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::Pause {}).unwrap();
+        env.block.time = start.plus_seconds(2000);
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::Resume {}).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::LockWindowExpired {}));
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        for round_id in [2, 3] {
+            let round: msg::RoundResponse =
+                from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetRoundDetails { round_id }).unwrap()).unwrap();
+            assert_eq!(round.status, msg::RoundStatus::Cancelled);
+        }
+        let res = execute(deps.as_mut(), env.clone(), message_info(&alice, &[]), ExecuteMsg::ClaimRefund { round_id: 3 }).unwrap();
+        assert_eq!(res.messages[0].msg, CosmosMsg::Bank(BankMsg::Send { to_address: alice.to_string(), amount: coins(10, "uzig") }));
+
+        env.block.time = start.plus_seconds(2300);
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        env.block.time = start.plus_seconds(2600);
+        execute(deps.as_mut(), env.clone(), admin, exec_msg).unwrap();
+        let round: msg::RoundResponse =
+            from_json(query(deps.as_ref(), env, QueryMsg::GetRoundDetails { round_id: 6 }).unwrap()).unwrap();
+        assert_eq!(round.status, msg::RoundStatus::Open);
+    }
+
+    #[test]
+    fn test_round_pipeline() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let admin = message_info(&addr, &[]);
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        // Rounds cannot be executed before the genesis rounds
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg).unwrap_err();
+        assert!(matches!(err, ContractError::GenesisNotLocked {}));
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::GenesisAlreadyStarted {}));
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 20);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 10);
+
+        env.block.time = start.plus_seconds(300);
+
+        // A manual oracle needs the admin to supply the price
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: None, signed_price: None };
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap_err();
+        assert!(matches!(err, ContractError::MissingPrice {}));
+
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        let res = execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "genesis_lock_round"),
+                attr("locked_round_id", "1"),
+                attr("started_round_id", "2")
+            ]
+        );
+
+        // Round 2 is open while round 1 is live
+        let bettor = deps.api.addr_make("bettor");
+        let bet = message_info(&bettor, &coins(20, "uzig"));
+        let bet_msg = ExecuteMsg::PlaceBet { round_id: 2, direction: msg::Direction::Up };
+        execute(deps.as_mut(), env.clone(), bet, bet_msg).unwrap();
+
+        env.block.time = start.plus_seconds(600);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(105u64)), signed_price: None };
+        let res = execute(deps.as_mut(), env.clone(), admin, exec_msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "execute_round"),
+                attr("locked_round_id", "2"),
+                attr("ended_round_id", "1"),
+                attr("started_round_id", "3"),
+                attr("keeper_reward", "0")
             ]
+        );
+
+        let ended = crate::state::ROUNDS.load(&deps.storage, 1).unwrap();
+        assert_eq!(ended.status, msg::RoundStatus::Settled);
+        assert_eq!(ended.result, Some(msg::Direction::Up));
+
+        // Round 2 only has bulls, so it is voided when it locks
+        let locked = crate::state::ROUNDS.load(&deps.storage, 2).unwrap();
+        assert_eq!(locked.lock_price, Uint128::from(105u64));
+        assert_eq!(locked.total_pool, Uint128::from(20u64));
+        assert_eq!(locked.status, msg::RoundStatus::Cancelled);
+
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.current_round_id, 3);
+    }
+
+    #[test]
+    fn test_update_config() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let msg = ExecuteMsg::UpdateConfig(msg::UpdateConfigMsg {
+            betting_interval: Some(280),
+            buffer_seconds: Some(20),
+            ..Default::default()
+        });
+
+        // Only the owner can update the config
+        let stranger = message_info(&deps.api.addr_make("stranger"), &[]);
+        let err = execute(deps.as_mut(), mock_env(), stranger, msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOwner {}));
+
+        let admin = message_info(&addr, &[]);
+        execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(config.betting_interval, 280);
+        assert_eq!(config.live_interval, 300);
+        assert_eq!(config.buffer_seconds, 20);
+
+        // Buffer must stay shorter than the intervals
+        let msg = ExecuteMsg::UpdateConfig(msg::UpdateConfigMsg {
+            buffer_seconds: Some(300),
+            ..Default::default()
+        });
+        let err = execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoundTiming {}));
+
+        // Intervals further apart than the buffer would never let a round settle
+        let msg = ExecuteMsg::UpdateConfig(msg::UpdateConfigMsg {
+            live_interval: Some(600),
+            ..Default::default()
+        });
+        let err = execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoundTiming {}));
+
+        // Round 2 locks from +560 to +580 and round 1 ends from +580 to +600, both are due
+        // at +580
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let mut env = mock_env();
+        let start = env.block.time;
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 20);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 10);
+
+        env.block.time = start.plus_seconds(280);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        bet(deps.as_mut(), &env, &alice, 2, msg::Direction::Up, 10);
+        bet(deps.as_mut(), &env, &bob, 2, msg::Direction::Down, 10);
+
+        env.block.time = start.plus_seconds(580);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin, exec_msg).unwrap();
+        let round: msg::RoundResponse = from_json(query(deps.as_ref(), env, QueryMsg::GetRoundDetails { round_id: 1 }).unwrap()).unwrap();
+        assert_eq!(round.status, msg::RoundStatus::Settled);
+    }
+
+    #[test]
+    fn test_round_status_lifecycle() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let admin = message_info(&addr, &[]);
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        let round_status = |deps: Deps, round_id: u64| -> msg::RoundStatus {
+            let msg = QueryMsg::GetRoundDetails { round_id };
+            let round: msg::RoundResponse = from_json(query(deps, mock_env(), msg).unwrap()).unwrap();
+            round.status
+        };
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        assert_eq!(round_status(deps.as_ref(), 1), msg::RoundStatus::Open);
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 20);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 20);
+
+        env.block.time = start.plus_seconds(300);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::zero()), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+        assert_eq!(round_status(deps.as_ref(), 1), msg::RoundStatus::Locked);
+        assert_eq!(round_status(deps.as_ref(), 2), msg::RoundStatus::Open);
+
+        bet(deps.as_mut(), &env, &alice, 2, msg::Direction::Up, 20);
+        bet(deps.as_mut(), &env, &bob, 2, msg::Direction::Down, 20);
+
+        // A zero price is a real price: the round still ends, as a tie
+        env.block.time = start.plus_seconds(600);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::zero()), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg).unwrap();
+        assert_eq!(round_status(deps.as_ref(), 1), msg::RoundStatus::Refunding);
+        assert_eq!(round_status(deps.as_ref(), 2), msg::RoundStatus::Locked);
+
+        // Betting on a locked round is rejected by its status
+        let bettor = message_info(&deps.api.addr_make("bettor"), &coins(20, "uzig"));
+        let bet_msg = ExecuteMsg::PlaceBet { round_id: 2, direction: msg::Direction::Up };
+        let err = execute(deps.as_mut(), env, bettor, bet_msg).unwrap_err();
+        assert!(matches!(err, ContractError::RoundLocked {}));
+    }
+
+    #[test]
+    fn test_cancel_round_and_refund() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let admin = message_info(&addr, &[]);
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 20);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 10);
+
+        // Refunds are only available once the round is voided
+        let alice_info = message_info(&alice, &[]);
+        let refund_msg = ExecuteMsg::ClaimRefund { round_id: 1 };
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), refund_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::RoundNotRefundable {}));
+
+        // Only the operator can cancel a round that is still on schedule
+        let cancel_msg = ExecuteMsg::CancelRound { round_id: 1 };
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), cancel_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOperator {}));
+
+        // Once the lock window has passed anyone can void the round
+        env.block.time = start.plus_seconds(331);
+        execute(deps.as_mut(), env.clone(), alice_info.clone(), cancel_msg.clone()).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), admin, cancel_msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidRoundTransition { .. }));
+
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), refund_msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(20, "uzig"),
+            })
+        );
+
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), refund_msg).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyClaimed {}));
+
+        let claim_msg = ExecuteMsg::ClaimReward { round_id: 1 };
+        let err = execute(deps.as_mut(), env, alice_info, claim_msg).unwrap_err();
+        assert!(matches!(err, ContractError::RoundVoided {}));
+    }
+
+    #[test]
+    fn test_winnings_ledger() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst_with_fee(deps.as_mut(), &addr, 0);
+
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+        let env = run_two_rounds(&mut deps, &addr);
+
+        let winnings = |deps: Deps, user: &Addr| -> Uint128 {
+            let msg = QueryMsg::GetWinnings { user: user.to_string() };
+            from_json(query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        // Settlement only records the result, bets are credited when they are processed
+        assert_eq!(winnings(deps.as_ref(), &alice), Uint128::zero());
+        let bob_info = message_info(&bob, &[]);
+        let process = |round_id| ExecuteMsg::ProcessSettlement { round_id, limit: 1 };
+        let res = execute(deps.as_mut(), env.clone(), bob_info.clone(), process(1)).unwrap();
+        assert_eq!(res.attributes[2], attr("processed", "1"));
+        assert_eq!(res.attributes[3], attr("done", "false"));
+        let res = execute(deps.as_mut(), env.clone(), bob_info.clone(), process(1)).unwrap();
+        assert_eq!(res.attributes[2], attr("processed", "1"));
+        let res = execute(deps.as_mut(), env.clone(), bob_info.clone(), process(1)).unwrap();
+        assert_eq!(res.attributes[2], attr("processed", "0"));
+        assert_eq!(res.attributes[3], attr("done", "true"));
+        assert_eq!(winnings(deps.as_ref(), &alice), Uint128::from(30u64));
+        assert_eq!(winnings(deps.as_ref(), &bob), Uint128::zero());
+
+        let msg = QueryMsg::GetLeaderboard { sort_by: None, limit: None, start_after: None };
+        let board: msg::LeaderboardResponse = from_json(query(deps.as_ref(), env.clone(), msg).unwrap()).unwrap();
+        let bob_board = board.entries.iter().find(|entry| entry.user == bob).unwrap();
+        assert_eq!((bob_board.total_lost, bob_board.amount_lost), (1, Uint128::from(10u64)));
+        assert_eq!(bob_board.total_amount_played, Uint128::from(20u64));
+
+        // Round 3 is still locked
+        let err = execute(deps.as_mut(), env.clone(), bob_info.clone(), process(3)).unwrap_err();
+        assert!(matches!(err, ContractError::RoundNotEnded {}));
+
+        // Claiming a round debits the ledger exactly once
+        let alice_info = message_info(&alice, &[]);
+        let claim_msg = ExecuteMsg::ClaimReward { round_id: 1 };
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), claim_msg.clone()).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(30, "uzig"),
+            })
+        );
+        assert_eq!(winnings(deps.as_ref(), &alice), Uint128::zero());
+
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), claim_msg).unwrap_err();
+        assert!(matches!(err, ContractError::AlreadyClaimed {}));
+
+        let err = execute(deps.as_mut(), env.clone(), bob_info.clone(), ExecuteMsg::ClaimReward { round_id: 1 })
+            .unwrap_err();
+        assert!(matches!(err, ContractError::BetLost {}));
+
+        // The remaining balance is withdrawn in one transfer
+        execute(deps.as_mut(), env.clone(), bob_info.clone(), ExecuteMsg::ProcessSettlement { round_id: 2, limit: 10 })
+            .unwrap();
+        assert_eq!(winnings(deps.as_ref(), &alice), Uint128::from(20u64));
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::WithdrawWinnings {})
+            .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(20, "uzig"),
+            })
+        );
+        assert_eq!(winnings(deps.as_ref(), &alice), Uint128::zero());
+
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::ClaimReward { round_id: 2 })
+            .unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientWinnings {}));
+
+        let err = execute(deps.as_mut(), env, alice_info, ExecuteMsg::WithdrawWinnings {}).unwrap_err();
+        assert!(matches!(err, ContractError::NoWinnings {}));
+    }
+
+    #[test]
+    fn test_claim_after_withdrawal() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst_with_fee(deps.as_mut(), &addr, 0);
+
+        let alice = deps.api.addr_make("alice");
+        let env = run_two_rounds(&mut deps, &addr);
+        let alice_info = message_info(&alice, &[]);
+        let paid = |res: Response| res.messages[0].msg.clone();
+
+        // Round 1 is credited and withdrawn without a claim
+        execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::ProcessSettlement { round_id: 1, limit: 10 })
+            .unwrap();
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::WithdrawWinnings {}).unwrap();
+        assert_eq!(paid(res), CosmosMsg::Bank(BankMsg::Send { to_address: alice.to_string(), amount: coins(30, "uzig") }));
+
+        // Claiming round 2 only pays its own credit, round 1 cannot be paid again
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), ExecuteMsg::ClaimReward { round_id: 2 }).unwrap();
+        assert_eq!(paid(res), CosmosMsg::Bank(BankMsg::Send { to_address: alice.to_string(), amount: coins(20, "uzig") }));
+        let err = execute(deps.as_mut(), env, alice_info, ExecuteMsg::ClaimReward { round_id: 1 }).unwrap_err();
+        assert!(matches!(err, ContractError::InsufficientWinnings {}));
+    }
+
+    #[test]
+    fn test_batch_claim_rewards() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst_with_fee(deps.as_mut(), &addr, 0);
+
+        let alice = deps.api.addr_make("alice");
+        let env = run_two_rounds(&mut deps, &addr);
+        let alice_info = message_info(&alice, &[]);
+
+        // Strict mode rejects the batch if any round cannot be claimed
+        let msg = ExecuteMsg::ClaimRewards { round_ids: vec![4, 1, 2], strict: true };
+        let err = execute(deps.as_mut(), env.clone(), alice_info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::RoundNotEnded {}));
+
+        // Otherwise the round still open is skipped and the rest paid at once
+        let msg = ExecuteMsg::ClaimRewards { round_ids: vec![1, 4, 2, 1], strict: false };
+        let res = execute(deps.as_mut(), env.clone(), alice_info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "claim_rewards"),
+                attr("user", alice.to_string()),
+                attr("round_id", "1"),
+                attr("reward", "30"),
+                attr("skipped_round_id", "4"),
+                attr("reason", "Round not ended"),
+                attr("round_id", "2"),
+                attr("reward", "20"),
+                attr("skipped_round_id", "1"),
+                attr("reason", "Already claimed"),
+                attr("total_reward", "50"),
+                attr("amount", "50")
+            ]
+        );
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(50, "uzig"),
+            })
+        );
+
+        let msg = ExecuteMsg::ClaimRewards { round_ids: vec![1, 2], strict: false };
+        let err = execute(deps.as_mut(), env, alice_info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+    }
+
+    #[test]
+    fn test_treasury_accounting() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let admin = message_info(&addr, &[]);
+        let treasury = deps.api.addr_make("treasury");
+        let msg = ExecuteMsg::UpdateRoles { operator: None, pauser: None, treasury: Some(treasury.to_string()) };
+        execute(deps.as_mut(), mock_env(), admin.clone(), msg).unwrap();
+
+        let mut env = mock_env();
+        let start = env.block.time;
+        let alice = deps.api.addr_make("alice");
+        let bob = deps.api.addr_make("bob");
+
+        execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::GenesisStartRound {}).unwrap();
+        bet(deps.as_mut(), &env, &alice, 1, msg::Direction::Up, 2000);
+        bet(deps.as_mut(), &env, &bob, 1, msg::Direction::Down, 1000);
+
+        env.block.time = start.plus_seconds(300);
+        let lock_msg = ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), lock_msg).unwrap();
+
+        // Nothing has accrued before settlement
+        let err = execute(deps.as_mut(), env.clone(), admin.clone(), ExecuteMsg::ClaimTreasury {}).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
+
+        env.block.time = start.plus_seconds(600);
+        let exec_msg = ExecuteMsg::ExecuteRound { price: Some(Uint128::from(110u64)), signed_price: None };
+        execute(deps.as_mut(), env.clone(), admin.clone(), exec_msg).unwrap();
+
+        // 2% of the 3000 pool goes to the treasury, alice gets the rest
+        let round = crate::state::ROUNDS.load(&deps.storage, 1).unwrap();
+        assert_eq!(round.reward_base, Uint128::from(2000u64));
+        assert_eq!(round.reward_amount, Uint128::from(2940u64));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&alice, &[]),
+            ExecuteMsg::ClaimReward { round_id: 1 },
         )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: alice.to_string(),
+                amount: coins(2940, "uzig"),
+            })
+        );
+
+        // Only the owner or the treasury may claim, and only the accrued fees
+        let err = execute(deps.as_mut(), env.clone(), message_info(&bob, &[]), ExecuteMsg::ClaimTreasury {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedTreasury {}));
+
+        let res = execute(deps.as_mut(), env.clone(), message_info(&treasury, &[]), ExecuteMsg::ClaimTreasury {})
+            .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: treasury.to_string(),
+                amount: coins(60, "uzig"),
+            })
+        );
+
+        let treasury_state: msg::TreasuryResponse =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetTreasury {}).unwrap()).unwrap();
+        assert_eq!(
+            treasury_state,
+            msg::TreasuryResponse {
+                treasury,
+                accrued: Uint128::from(60u64),
+                withdrawn: Uint128::from(60u64),
+                available: Uint128::zero(),
+            }
+        );
+
+        let err = execute(deps.as_mut(), env, admin, ExecuteMsg::ClaimTreasury {}).unwrap_err();
+        assert!(matches!(err, ContractError::NothingToClaim {}));
     }
 
+    #[test]
+    fn test_place_bet_funds_validation() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), message_info(&addr, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+
+        let bettor = deps.api.addr_make("bettor");
+        let bet_msg = ExecuteMsg::PlaceBet { round_id: 1, direction: msg::Direction::Up };
+
+        let info = message_info(&bettor, &[]);
+        let err = execute(deps.as_mut(), env.clone(), info, bet_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::NoFundSent {}));
+
+        let info = message_info(&bettor, &coins(20, "uatom"));
+        let err = execute(deps.as_mut(), env.clone(), info, bet_msg.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid denom: expected uzig, received uatom");
+
+        let info = message_info(&bettor, &[coin(20, "uzig"), coin(5, "uatom")]);
+        let err = execute(deps.as_mut(), env.clone(), info, bet_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::MixedDenoms {}));
+
+        let info = message_info(&bettor, &coins(20, "uzig"));
+        execute(deps.as_mut(), env, info, bet_msg).unwrap();
+    }
+
+    #[test]
+    fn test_cw20_stake_asset() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        let token = deps.api.addr_make("token");
+        let msg = InstantiateMsg {
+            stake_asset: StakeAsset::Cw20 { contract_addr: token.clone() },
+            oracle: OracleSource::Manual,
+            treasury_fee: 200,
+            operator: None,
+            pauser: None,
+            treasury: None,
+            keeper_reward: 0,
+            min_bet: Uint128::zero(),
+            max_bet: None,
+            max_user_stake: None,
+            max_round_pool: None,
+            cancel_fee: 0,
+            betting_interval: 300,
+            live_interval: 300,
+            buffer_seconds: 30,
+        };
+        instantiate(deps.as_mut(), mock_env(), message_info(&addr, &[]), msg).unwrap();
+
+        let env = mock_env();
+        execute(deps.as_mut(), env.clone(), message_info(&addr, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+
+        // Native funds are rejected in a CW20 market
+        let bettor = deps.api.addr_make("bettor");
+        let bet_msg = ExecuteMsg::PlaceBet { round_id: 1, direction: msg::Direction::Up };
+        let err = execute(deps.as_mut(), env.clone(), message_info(&bettor, &coins(20, "uzig")), bet_msg)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::WrongStakeAsset {}));
+
+        let receive_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: bettor.to_string(),
+            amount: Uint128::from(20u64),
+            msg: to_json_binary(&msg::ReceiveMsg::PlaceBet {
+                round_id: 1,
+                direction: msg::Direction::Up,
+            })
+            .unwrap(),
+        });
+
+        // Only the configured token can call the hook
+        let other_token = message_info(&deps.api.addr_make("other_token"), &[]);
+        let err = execute(deps.as_mut(), env.clone(), other_token, receive_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::WrongStakeAsset {}));
+
+        let res = execute(deps.as_mut(), env.clone(), message_info(&token, &[]), receive_msg).unwrap();
+        assert_eq!(res.attributes[2], attr("user", bettor.to_string()));
+        assert_eq!(res.attributes[3], attr("amount", "20"));
+
+        // Refunds are paid back as CW20 transfers
+        execute(deps.as_mut(), env.clone(), message_info(&addr, &[]), ExecuteMsg::CancelRound { round_id: 1 })
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env,
+            message_info(&bettor, &[]),
+            ExecuteMsg::ClaimRefund { round_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: token.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: bettor.to_string(),
+                    amount: Uint128::from(20u64),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    fn sign_secp256k1(key: &k256::ecdsa::SigningKey, message: &[u8]) -> PriceSignature {
+        use k256::ecdsa::{signature::Signer, Signature};
+
+        let signature: Signature = key.sign(message);
+        PriceSignature {
+            signer: Binary::from(key.verifying_key().to_encoded_point(true).as_bytes()),
+            signature: Binary::from(signature.to_bytes().to_vec()),
+        }
+    }
+
+    #[test]
+    fn test_signed_price_submissions() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        let keys: Vec<k256::ecdsa::SigningKey> = (1u8..=3)
+            .map(|i| k256::ecdsa::SigningKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let signers = keys
+            .iter()
+            .map(|key| Binary::from(key.verifying_key().to_encoded_point(true).as_bytes()))
+            .collect();
+        let msg = InstantiateMsg {
+            stake_asset: StakeAsset::Native { denom: "uzig".to_string() },
+            oracle: OracleSource::Signed { signers, threshold: 2, scheme: SignatureScheme::Secp256k1, max_age: 60 },
+            treasury_fee: 200,
+            operator: None,
+            pauser: None,
+            treasury: None,
+            keeper_reward: 0,
+            min_bet: Uint128::zero(),
+            max_bet: None,
+            max_user_stake: None,
+            max_round_pool: None,
+            cancel_fee: 0,
+            betting_interval: 300,
+            live_interval: 300,
+            buffer_seconds: 30,
+        };
+        instantiate(deps.as_mut(), mock_env(), message_info(&addr, &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        execute(deps.as_mut(), env.clone(), message_info(&addr, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+        env.block.time = env.block.time.plus_seconds(300);
+        let now = env.block.time.seconds();
+
+        let sign = |signers: &[&k256::ecdsa::SigningKey], timestamp: u64, nonce: u64| {
+            let message = signed_price_message(&env, Uint128::from(100u64), timestamp, nonce).unwrap();
+            SignedPrice {
+                price: Uint128::from(100u64),
+                timestamp,
+                nonce,
+                signatures: signers.iter().map(|key| sign_secp256k1(key, &message)).collect(),
+            }
+        };
+        let lock = |signed_price| ExecuteMsg::GenesisLockRound { price: None, signed_price: Some(signed_price) };
+        let relayer = message_info(&deps.api.addr_make("relayer"), &[]);
+
+        // Plain prices are not accepted from anyone
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            message_info(&addr, &[]),
+            ExecuteMsg::GenesisLockRound { price: Some(Uint128::from(100u64)), signed_price: None },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ManualPriceNotAllowed {}));
+
+        // The same signer twice, or an unknown signer, does not reach the threshold
+        let outsider = k256::ecdsa::SigningKey::from_slice(&[9; 32]).unwrap();
+        let msg = lock(sign(&[&keys[0], &keys[0], &outsider], now, 1));
+        let err = execute(deps.as_mut(), env.clone(), relayer.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughSignatures { valid: 1, required: 2 }));
+
+        // Signatures over another price are not counted
+        let mut tampered = sign(&[&keys[0], &keys[1]], now, 1);
+        tampered.price = Uint128::from(101u64);
+        let err = execute(deps.as_mut(), env.clone(), relayer.clone(), lock(tampered)).unwrap_err();
+        assert!(matches!(err, ContractError::NotEnoughSignatures { valid: 0, required: 2 }));
+
+        let msg = lock(sign(&[&keys[0], &keys[1]], now - 61, 1));
+        let err = execute(deps.as_mut(), env.clone(), relayer.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::StalePrice { age: 61 }));
+
+        let msg = lock(sign(&[&keys[0], &keys[1]], now + 1, 1));
+        let err = execute(deps.as_mut(), env.clone(), relayer.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidPriceTimestamp {}));
+
+        // A price observed before the round's lock time cannot be picked
+        let msg = lock(sign(&[&keys[0], &keys[1]], now - 10, 1));
+        let err = execute(deps.as_mut(), env.clone(), relayer.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::PriceBeforeRound {}));
+
+        // Anyone can relay a price signed by 2 of the 3 signers
+        let signed = sign(&[&keys[1], &keys[2]], now, 1);
+        execute(deps.as_mut(), env.clone(), relayer.clone(), lock(signed.clone())).unwrap();
+        let round: msg::RoundResponse =
+            from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetRoundDetails { round_id: 1 }).unwrap()).unwrap();
+        assert_eq!(round.lock_price, Uint128::from(100u64));
+
+        // A signed price cannot be replayed
+        env.block.time = env.block.time.plus_seconds(300);
+        let exec = ExecuteMsg::ExecuteRound { price: None, signed_price: Some(signed) };
+        let err = execute(deps.as_mut(), env.clone(), relayer.clone(), exec).unwrap_err();
+        assert!(matches!(err, ContractError::PriceNonceUsed {}));
+    }
+
+    #[test]
+    fn test_signed_price_ed25519() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        let key = ed25519_zebra::SigningKey::from([7u8; 32]);
+        let signer = Binary::from(<[u8; 32]>::from(ed25519_zebra::VerificationKey::from(&key)).to_vec());
+        let msg = InstantiateMsg {
+            stake_asset: StakeAsset::Native { denom: "uzig".to_string() },
+            oracle: OracleSource::Signed {
+                signers: vec![signer.clone()],
+                threshold: 1,
+                scheme: SignatureScheme::Ed25519,
+                max_age: 60,
+            },
+            treasury_fee: 200,
+            operator: None,
+            pauser: None,
+            treasury: None,
+            keeper_reward: 0,
+            min_bet: Uint128::zero(),
+            max_bet: None,
+            max_user_stake: None,
+            max_round_pool: None,
+            cancel_fee: 0,
+            betting_interval: 300,
+            live_interval: 300,
+            buffer_seconds: 30,
+        };
+        instantiate(deps.as_mut(), mock_env(), message_info(&addr, &[]), msg).unwrap();
+
+        let mut env = mock_env();
+        execute(deps.as_mut(), env.clone(), message_info(&addr, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+        env.block.time = env.block.time.plus_seconds(300);
+
+        let timestamp = env.block.time.seconds();
+        let message = signed_price_message(&env, Uint128::from(100u64), timestamp, 7).unwrap();
+        let signature = <[u8; 64]>::from(key.sign(&message));
+        let signed_price = SignedPrice {
+            price: Uint128::from(100u64),
+            timestamp,
+            nonce: 7,
+            signatures: vec![PriceSignature { signer, signature: Binary::from(signature.to_vec()) }],
+        };
+        let msg = ExecuteMsg::GenesisLockRound { price: None, signed_price: Some(signed_price) };
+        let relayer = message_info(&deps.api.addr_make("relayer"), &[]);
+        execute(deps.as_mut(), env, relayer, msg).unwrap();
+    }
+
+    #[test]
+    fn test_roles_and_ownership_transfer() {
+        let mut deps = mock_dependencies();
+        let addr = deps.api.addr_make("creator");
+        inst(deps.as_mut(), &addr);
+
+        let owner = message_info(&addr, &[]);
+        let operator = deps.api.addr_make("operator");
+        let pauser = deps.api.addr_make("pauser");
+        let new_owner = deps.api.addr_make("new_owner");
+        let mut env = mock_env();
+
+        // Roles are only reassigned by the owner
+        let roles_msg = ExecuteMsg::UpdateRoles {
+            operator: Some(operator.to_string()),
+            pauser: Some(pauser.to_string()),
+            treasury: None,
+        };
+        let err = execute(deps.as_mut(), env.clone(), message_info(&operator, &[]), roles_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOwner {}));
+        execute(deps.as_mut(), env.clone(), owner.clone(), roles_msg).unwrap();
+
+        // Each role can only do its own job
+        let err = execute(deps.as_mut(), env.clone(), owner.clone(), ExecuteMsg::GenesisStartRound {}).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOperator {}));
+        execute(deps.as_mut(), env.clone(), message_info(&operator, &[]), ExecuteMsg::GenesisStartRound {}).unwrap();
+        let err = execute(deps.as_mut(), env.clone(), message_info(&operator, &[]), ExecuteMsg::Pause {}).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedPauser {}));
+        execute(deps.as_mut(), env.clone(), message_info(&pauser, &[]), ExecuteMsg::Pause {}).unwrap();
+
+        // Ownership moves only once the new owner accepts, before the offer expires
+        let transfer_msg = ExecuteMsg::TransferOwnership { new_owner: new_owner.to_string(), expires_in: 100 };
+        let err = execute(deps.as_mut(), env.clone(), message_info(&new_owner, &[]), transfer_msg.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOwner {}));
+        execute(deps.as_mut(), env.clone(), owner.clone(), transfer_msg.clone()).unwrap();
+
+        let err = execute(deps.as_mut(), env.clone(), message_info(&operator, &[]), ExecuteMsg::AcceptOwnership {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedPendingOwner {}));
+
+        env.block.time = env.block.time.plus_seconds(101);
+        let err = execute(deps.as_mut(), env.clone(), message_info(&new_owner, &[]), ExecuteMsg::AcceptOwnership {})
+            .unwrap_err();
+        assert!(matches!(err, ContractError::OwnershipTransferExpired {}));
+
+        execute(deps.as_mut(), env.clone(), owner.clone(), transfer_msg).unwrap();
+        execute(deps.as_mut(), env.clone(), message_info(&new_owner, &[]), ExecuteMsg::AcceptOwnership {}).unwrap();
+
+        let roles: msg::RolesResponse = from_json(query(deps.as_ref(), env.clone(), QueryMsg::GetRoles {}).unwrap()).unwrap();
+        assert_eq!(
+            roles,
+            msg::RolesResponse {
+                owner: new_owner,
+                operator,
+                pauser,
+                treasury: addr.clone(),
+                pending_owner: None,
+                pending_owner_expires_at: None,
+            }
+        );
+
+        // The previous owner has lost its rights
+        let err = execute(deps.as_mut(), env, owner, ExecuteMsg::CancelOwnershipTransfer {}).unwrap_err();
+        assert!(matches!(err, ContractError::UnauthorizedOwner {}));
+    }
 }
@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
+use crate::msg::RoundStatus;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,8 +7,32 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
-    #[error("Unauthorized")]
-    Unauthorized {},
+    #[error("Cannot migrate from contract {found}, expected {expected}")]
+    InvalidContractName { expected: String, found: String },
+
+    #[error("Cannot migrate from version {from} down to {to}")]
+    CannotMigrateDowngrade { from: String, to: String },
+
+    #[error("Unauthorized: caller is not the owner")]
+    UnauthorizedOwner {},
+
+    #[error("Unauthorized: caller is not the operator")]
+    UnauthorizedOperator {},
+
+    #[error("Unauthorized: caller is not the pauser")]
+    UnauthorizedPauser {},
+
+    #[error("Unauthorized: caller is not the treasury")]
+    UnauthorizedTreasury {},
+
+    #[error("Unauthorized: caller is not the pending owner")]
+    UnauthorizedPendingOwner {},
+
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner {},
+
+    #[error("Ownership transfer has expired")]
+    OwnershipTransferExpired {},
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 
@@ -20,12 +45,36 @@ pub enum ContractError {
     #[error("Round locked")]
     RoundLocked {},
 
-    #[error("User already placed bet")]
-    BetAlreadyPlaced {},
+    #[error("Bets can only be increased in the same direction")]
+    BetDirectionMismatch {},
+
+    #[error("Bet is below the minimum of {min}")]
+    BetBelowMinimum { min: Uint128 },
+
+    #[error("Bet is above the maximum of {max}")]
+    BetAboveMaximum { max: Uint128 },
+
+    #[error("Stake of a user in a round cannot exceed {cap}")]
+    UserStakeCapExceeded { cap: Uint128 },
+
+    #[error("Total pool of a round cannot exceed {cap}")]
+    RoundPoolCapExceeded { cap: Uint128 },
+
+    #[error("Minimum bet must not exceed the maximum bet, user stake or round pool")]
+    InvalidBetLimits {},
 
     #[error("No fund sent")]
     NoFundSent {},
 
+    #[error("Invalid denom: expected {expected}, received {received}")]
+    InvalidDenom { expected: String, received: String },
+
+    #[error("Bets must be paid with the market's stake asset")]
+    WrongStakeAsset {},
+
+    #[error("Bets must be paid in a single denom")]
+    MixedDenoms {},
+
     #[error("Round not ended")]
     RoundNotEnded {},
 
@@ -46,4 +95,109 @@ pub enum ContractError {
 
     #[error("Cannnot start new round")]
     CannotStartNewRound {},
+
+    #[error("Invalid round transition from {from:?} to {to:?}")]
+    InvalidRoundTransition { from: RoundStatus, to: RoundStatus },
+
+    #[error("Round was voided, claim a refund instead")]
+    RoundVoided {},
+
+    #[error("Round is not refundable")]
+    RoundNotRefundable {},
+
+    #[error("Already claimed")]
+    AlreadyClaimed {},
+
+    #[error("Bet did not win")]
+    BetLost {},
+
+    #[error("Claimable winnings already withdrawn")]
+    InsufficientWinnings {},
+
+    #[error("Nothing to claim")]
+    NothingToClaim {},
+
+    #[error("No winnings to withdraw")]
+    NoWinnings {},
+
+    #[error("A price is required with a manual oracle")]
+    MissingPrice {},
+
+    #[error("Prices are read from the oracle and cannot be submitted")]
+    ManualPriceNotAllowed {},
+
+    #[error("Oracle price is stale ({age} seconds old)")]
+    StalePrice { age: u64 },
+
+    #[error("Oracle price confidence interval is too wide")]
+    PriceConfidenceTooLow {},
+
+    #[error("Keeper reward must be <= 10000 basis points")]
+    InvalidKeeperReward {},
+
+    #[error("Cancel fee must be <= 10000 basis points")]
+    InvalidCancelFee {},
+
+    #[error("A signed price is required with a signed oracle")]
+    MissingSignedPrice {},
+
+    #[error("Signed prices are only accepted with a signed oracle")]
+    SignedPriceNotAllowed {},
+
+    #[error("Signed price timestamp is in the future")]
+    InvalidPriceTimestamp {},
+
+    #[error("Price was observed before the round's lock or end time")]
+    PriceBeforeRound {},
+
+    #[error("Signed price nonce already used")]
+    PriceNonceUsed {},
+
+    #[error("Not enough valid price signatures: {valid} of {required}")]
+    NotEnoughSignatures { valid: u32, required: u32 },
+
+    #[error("Genesis round already started")]
+    GenesisAlreadyStarted {},
+
+    #[error("Genesis round not started")]
+    GenesisNotStarted {},
+
+    #[error("Genesis round already locked")]
+    GenesisAlreadyLocked {},
+
+    #[error("Genesis round not locked")]
+    GenesisNotLocked {},
+
+    #[error("Lock window expired")]
+    LockWindowExpired {},
+
+    #[error("End window expired")]
+    EndWindowExpired {},
+
+    #[error("Invalid round timing: intervals must be non-zero, longer than the buffer and differ by at most the buffer")]
+    InvalidRoundTiming {},
+
+    #[error("Season not found")]
+    SeasonNotFound {},
+
+    #[error("Seasons must start in the future, end after they start and not overlap")]
+    InvalidSeasonTime {},
+
+    #[error("Season fee share must be <= 10000 basis points")]
+    InvalidFeeShare {},
+
+    #[error("Payout curve must not be empty and add up to at most 10000 basis points")]
+    InvalidPayoutCurve {},
+
+    #[error("Season has ended")]
+    SeasonEnded {},
+
+    #[error("Season is not closed yet")]
+    SeasonNotClosed {},
+
+    #[error("No prize for this season")]
+    NoSeasonPrize {},
+
+    #[error("Season prize pool already swept")]
+    SeasonAlreadySwept {},
 }
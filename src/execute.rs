@@ -1,9 +1,22 @@
 use crate::error::ContractError;
-use crate::msg::{Direction, ExecuteMsg, LeaderboardEntry};
-use crate::state::{Bet, Config, Round, CONFIG, LEADERBOARDENTRY, ROUNDS, USER_BETS};
+use crate::msg::{
+    Direction, ExecuteMsg, LeaderboardEntry, ReceiveMsg, RoundStatus, UpdateConfigMsg,
+};
+use crate::oracle::{resolve_price, SignedPrice};
+use crate::payout;
+use crate::payout::bet_reward;
+use crate::season;
+use crate::state::{
+    Bet, Config, PendingOwner, Roles, Round, RoundPayout, StakeAsset, TreasuryBalance, CONFIG,
+    LEADERBOARDENTRY, PENDING_OWNER, ROLES, ROUNDS, ROUND_PAYOUTS, SETTLEMENT_CURSOR,
+    TREASURY_BALANCE, USER_BETS, WINNINGS,
+};
 use cosmwasm_std::{
-    BankMsg, Coin, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Timestamp, Uint128,
+    from_json, Addr, Coin, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult,
+    Storage, Timestamp, Uint128,
 };
+use cw20::Cw20ReceiveMsg;
+use cw_storage_plus::Bound;
 
 pub fn execute(
     deps: DepsMut,
@@ -13,199 +26,571 @@ pub fn execute(
 ) -> Result<Response, ContractError> {
     // <match the input message to the execute function
     match msg {
-        ExecuteMsg::StartRound { price } => execute_start_round(deps, env, info, price),
-        ExecuteMsg::LockRound { price } => execute_lock_round(deps, info, env, price),
-        ExecuteMsg::EndRound { price } => execute_end_round(deps, env, info, price),
+        ExecuteMsg::GenesisStartRound {} => execute_genesis_start_round(deps, env, info),
+        ExecuteMsg::GenesisLockRound {
+            price,
+            signed_price,
+        } => execute_genesis_lock_round(deps, env, info, price, signed_price),
+        ExecuteMsg::ExecuteRound {
+            price,
+            signed_price,
+        } => execute_round(deps, env, info, price, signed_price),
         ExecuteMsg::Pause {} => execute_pause(deps, env, info),
         ExecuteMsg::Resume {} => execute_resume(deps, env, info),
-        ExecuteMsg::Withdraw { amount, recipient } => {
-            execute_withdraw(deps, env, info, amount, recipient)
-        }
+        ExecuteMsg::ClaimTreasury {} => execute_claim_treasury(deps, env, info),
         ExecuteMsg::PlaceBet {
             round_id,
             direction,
         } => execute_place_bet(deps, env, info, round_id, direction),
+        ExecuteMsg::Receive(wrapper) => execute_receive(deps, env, info, wrapper),
         ExecuteMsg::ClaimReward { round_id } => execute_claim_reward(deps, env, info, round_id),
+        ExecuteMsg::ClaimRewards { round_ids, strict } => {
+            execute_claim_rewards(deps, env, info, round_ids, strict)
+        }
+        ExecuteMsg::WithdrawWinnings {} => execute_withdraw_winnings(deps, env, info),
+        ExecuteMsg::ProcessSettlement { round_id, limit } => {
+            execute_process_settlement(deps, round_id, limit)
+        }
+        ExecuteMsg::ClaimRefund { round_id } => execute_claim_refund(deps, env, info, round_id),
+        ExecuteMsg::CancelBet { round_id } => execute_cancel_bet(deps, env, info, round_id),
+        ExecuteMsg::CancelRound { round_id } => execute_cancel_round(deps, env, info, round_id),
+        ExecuteMsg::UpdateConfig(update) => execute_update_config(deps, env, info, update),
+        ExecuteMsg::UpdateRoles {
+            operator,
+            pauser,
+            treasury,
+        } => execute_update_roles(deps, info, operator, pauser, treasury),
+        ExecuteMsg::TransferOwnership {
+            new_owner,
+            expires_in,
+        } => execute_transfer_ownership(deps, env, info, new_owner, expires_in),
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
+        ExecuteMsg::CancelOwnershipTransfer {} => execute_cancel_ownership_transfer(deps, info),
+        ExecuteMsg::CreateSeason {
+            start_time,
+            end_time,
+            fee_share,
+            payout_curve,
+        } => season::execute_create_season(
+            deps,
+            env,
+            info,
+            start_time,
+            end_time,
+            fee_share,
+            payout_curve,
+        ),
+        ExecuteMsg::FundSeason { season_id } => {
+            season::execute_fund_season(deps, env, info, season_id)
+        }
+        ExecuteMsg::ClaimSeasonPrize { season_id } => {
+            season::execute_claim_season_prize(deps, env, info, season_id)
+        }
+        ExecuteMsg::SweepSeason { season_id } => {
+            season::execute_sweep_season(deps, env, info, season_id)
+        }
     }
     // Ok(Response)
 }
 
 // ADMINN EXECUTE FUNCTIONS
-fn execute_start_round(
+fn execute_genesis_start_round(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
-    price: Uint128,
 ) -> Result<Response, ContractError> {
-    let config: Config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
+    let mut config: Config = CONFIG.load(deps.storage)?;
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.operator {
+        return Err(ContractError::UnauthorizedOperator {});
+    }
+    if config.paused {
+        return Err(ContractError::ContractPaused {});
+    }
+    if config.genesis_start_once {
+        // A stalled pipeline is restarted, its running rounds can no longer all be
+        // settled so they are voided and refunded
+        if !is_stalled(deps.storage, &config, &env)? {
+            return Err(ContractError::GenesisAlreadyStarted {});
+        }
+        for round_id in [config.current_round_id - 1, config.current_round_id] {
+            void_running_round(deps.storage, round_id)?;
+        }
+        config.genesis_lock_once = false;
+    }
+
+    // Open the first round of the pipeline, there is no price yet
+    config.current_round_id += 1;
+    start_round(
+        deps.storage,
+        &config,
+        &env,
+        config.current_round_id,
+        Uint128::zero(),
+    )?;
+
+    config.genesis_start_once = true;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "genesis_start_round")
+        .add_attribute("round_id", config.current_round_id.to_string()))
+}
+
+fn execute_genesis_lock_round(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    price: Option<Uint128>,
+    signed_price: Option<SignedPrice>,
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+    let roles: Roles = ROLES.load(deps.storage)?;
+    // With a trusted price source any keeper can progress the rounds
+    if info.sender != roles.operator && !config.is_permissionless() {
+        return Err(ContractError::UnauthorizedOperator {});
+    }
+    if config.paused {
+        return Err(ContractError::ContractPaused {});
+    }
+    if !config.genesis_start_once {
+        return Err(ContractError::GenesisNotStarted {});
+    }
+    if config.genesis_lock_once {
+        return Err(ContractError::GenesisAlreadyLocked {});
+    }
+
+    // Lock the genesis round and open the next one
+    let locked_round_id = config.current_round_id;
+    let locked: Round = ROUNDS
+        .may_load(deps.storage, locked_round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
+    let price: Uint128 = resolve_price(
+        deps.branch(),
+        &env,
+        &config.oracle,
+        price,
+        signed_price,
+        locked.lock_time,
+    )?;
+
+    lock_round(deps.storage, &config, &env, locked_round_id, price)?;
+
+    config.current_round_id += 1;
+    start_round(deps.storage, &config, &env, config.current_round_id, price)?;
+
+    config.genesis_lock_once = true;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "genesis_lock_round")
+        .add_attribute("locked_round_id", locked_round_id.to_string())
+        .add_attribute("started_round_id", config.current_round_id.to_string()))
+}
+
+fn execute_round(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    price: Option<Uint128>,
+    signed_price: Option<SignedPrice>,
+) -> Result<Response, ContractError> {
+    let mut config: Config = CONFIG.load(deps.storage)?;
+    let roles: Roles = ROLES.load(deps.storage)?;
+    // With a trusted price source any keeper can progress the rounds
+    if info.sender != roles.operator && !config.is_permissionless() {
+        return Err(ContractError::UnauthorizedOperator {});
+    }
+    if config.paused {
+        return Err(ContractError::ContractPaused {});
+    }
+    if !config.genesis_start_once || !config.genesis_lock_once {
+        return Err(ContractError::GenesisNotLocked {});
+    }
+
+    // Lock round N, end round N-1 and open round N+1 with the same price, observed once
+    // both are due
+    let locked_round_id = config.current_round_id;
+    let ended_round_id = locked_round_id - 1;
+    let locked: Round = ROUNDS
+        .may_load(deps.storage, locked_round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
+    let ended: Round = ROUNDS
+        .may_load(deps.storage, ended_round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
+    let price: Uint128 = resolve_price(
+        deps.branch(),
+        &env,
+        &config.oracle,
+        price,
+        signed_price,
+        locked.lock_time.max(ended.end_time),
+    )?;
+
+    lock_round(deps.storage, &config, &env, locked_round_id, price)?;
+    let keeper_reward = end_round(deps.storage, &config, &env, ended_round_id, price)?;
+
+    config.current_round_id += 1;
+    start_round(deps.storage, &config, &env, config.current_round_id, price)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "execute_round")
+        .add_attribute("locked_round_id", locked_round_id.to_string())
+        .add_attribute("ended_round_id", ended_round_id.to_string())
+        .add_attribute("started_round_id", config.current_round_id.to_string())
+        .add_attribute("keeper_reward", keeper_reward.to_string());
+    if !keeper_reward.is_zero() {
+        response = response.add_message(
+            config
+                .stake_asset
+                .transfer_msg(&info.sender, keeper_reward)?,
+        );
+    }
+    Ok(response)
+}
+
+/// The pipeline is stalled once the current or the previous round missed the window to
+/// lock or end it, ExecuteRound can then only fail
+fn is_stalled(storage: &dyn Storage, config: &Config, env: &Env) -> StdResult<bool> {
+    for round_id in [config.current_round_id - 1, config.current_round_id] {
+        if let Some(round) = ROUNDS.may_load(storage, round_id)? {
+            if has_expired(&round, config, env) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// A running round has expired once its lock or end window has passed
+fn has_expired(round: &Round, config: &Config, env: &Env) -> bool {
+    match round.status {
+        RoundStatus::Open => env.block.time > round.lock_time.plus_seconds(config.buffer_seconds),
+        RoundStatus::Locked => env.block.time > round.end_time.plus_seconds(config.buffer_seconds),
+        _ => false,
+    }
+}
+
+/// Cancels `round_id` if it has not ended yet
+fn void_running_round(storage: &mut dyn Storage, round_id: u64) -> Result<(), ContractError> {
+    let Some(mut round) = ROUNDS.may_load(storage, round_id)? else {
+        return Ok(());
+    };
+    if round.is_ended() {
+        return Ok(());
+    }
+
+    transition(&mut round, RoundStatus::Cancelled)?;
+    ROUNDS.save(storage, round_id, &round)?;
+    season::record_void(storage, &round)?;
+    Ok(())
+}
+
+/// Opens `round_id` for betting. The round two epochs back must already be ended.
+fn start_round(
+    storage: &mut dyn Storage,
+    config: &Config,
+    env: &Env,
+    round_id: u64,
+    price: Uint128,
+) -> Result<(), ContractError> {
+    if round_id > 2 {
+        let prev_round: Round = ROUNDS
+            .may_load(storage, round_id - 2)?
+            .ok_or(ContractError::RoundNotFound {})?;
+        if !prev_round.is_ended() {
+            return Err(ContractError::CannotStartNewRound {});
+        }
     }
 
     // Timestaps
     let beginning: Timestamp = env.block.time;
-    let lock: Timestamp = Timestamp::plus_minutes(&beginning, 5);
-    let end: Timestamp = Timestamp::plus_minutes(&beginning, 5);
+    let lock: Timestamp = beginning.plus_seconds(config.betting_interval);
+    let end: Timestamp = lock.plus_seconds(config.live_interval);
 
-    let round: Round = Round {
-        id: config.current_round_id,
+    let mut round: Round = Round {
+        id: round_id,
         bull_pool: Uint128::zero(),
         bear_pool: Uint128::zero(),
         total_pool: Uint128::zero(),
         start_time: beginning,
         lock_time: lock,
         end_time: end,
-        // replace start price with actual value
         start_price: price,
         lock_price: Uint128::zero(),
         end_price: Uint128::zero(),
         result: None,
-        executed: false,
-        participants: vec![],
+        reward_base: Uint128::zero(),
+        reward_amount: Uint128::zero(),
+        status: RoundStatus::Pending,
+        participant_count: 0,
     };
+    transition(&mut round, RoundStatus::Open)?;
+    ROUNDS.save(storage, round_id, &round)?;
 
-    // Validate rounds
-    if config.current_round_id == 1 {
-        ROUNDS.save(deps.storage, config.current_round_id, &round)?;
-    } else {
-        let previous_round = config.current_round_id - 1;
-        let prev_round: Round = ROUNDS.load(deps.storage, previous_round)?;
-
-        if config.current_round_id > 1 && prev_round.end_price != Uint128::zero() {
-            ROUNDS.save(deps.storage, config.current_round_id, &round)?;
-        } else {
-            return Err(ContractError::CannotStartNewRound {});
-        }
-    }
-
-    Ok(Response::new()
-        .add_attribute("action", "start_round")
-        .add_attribute("round_id", config.current_round_id.to_string()))
+    Ok(())
 }
 
-fn execute_lock_round(
-    deps: DepsMut,
-    info: MessageInfo,
-    env: Env,
+/// Records the lock price of `round_id` and starts its live interval
+fn lock_round(
+    storage: &mut dyn Storage,
+    config: &Config,
+    env: &Env,
+    round_id: u64,
     price: Uint128,
-) -> Result<Response, ContractError> {
-    let config: Config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
-    }
+) -> Result<(), ContractError> {
+    let mut round: Round = ROUNDS
+        .may_load(storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
 
-    let round: Round = ROUNDS.load(deps.storage, config.current_round_id)?;
-    if round.start_time == Timestamp::from_seconds(0) {
-        return Err(ContractError::RoundNotStarted {});
+    // A cancelled round stays voided, the pipeline moves past it
+    if round.status == RoundStatus::Cancelled {
+        return Ok(());
     }
 
     if env.block.time < round.lock_time {
         return Err(ContractError::CannotLockBeforeTime {});
     }
+    if env.block.time > round.lock_time.plus_seconds(config.buffer_seconds) {
+        return Err(ContractError::LockWindowExpired {});
+    }
 
-    ROUNDS.update(
-        deps.storage,
-        config.current_round_id,
-        |round| -> StdResult<_> {
-            let mut r: Round = round.ok_or(StdError::generic_err("Round not found"))?;
-            r.lock_price = price;
-            Ok(r)
-        },
-    )?;
+    transition(&mut round, RoundStatus::Locked)?;
+    round.lock_price = price;
+    round.end_time = env.block.time.plus_seconds(config.live_interval);
 
-    Ok(Response::new()
-        .add_attribute("action", "lock_round")
-        .add_attribute("round_id", config.current_round_id.to_string()))
+    // Nobody can be paid out of an empty side, so the round is voided
+    if round.bull_pool.is_zero() || round.bear_pool.is_zero() {
+        transition(&mut round, RoundStatus::Cancelled)?;
+        season::record_void(storage, &round)?;
+    }
+    ROUNDS.save(storage, round_id, &round)?;
+
+    Ok(())
 }
 
-fn execute_end_round(
-    deps: DepsMut,
-    env: Env,
-    info: MessageInfo,
+/// Records the end price of `round_id`, decides the result and updates the leaderboard
+fn end_round(
+    storage: &mut dyn Storage,
+    config: &Config,
+    env: &Env,
+    round_id: u64,
     price: Uint128,
-) -> Result<Response, ContractError> {
-    let config: Config = CONFIG.load(deps.storage)?;
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
-    }
+) -> Result<Uint128, ContractError> {
+    let mut round: Round = ROUNDS
+        .may_load(storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
 
-    let round: Round = ROUNDS.load(deps.storage, config.current_round_id)?;
-    if round.lock_price == Uint128::zero() {
+    if round.status == RoundStatus::Cancelled {
+        return Ok(Uint128::zero());
+    }
+    if round.status != RoundStatus::Locked {
         return Err(ContractError::CannnotEndWithoutLockPrice {});
     }
 
     if env.block.time < round.end_time {
         return Err(ContractError::CannotEndBeforeTime {});
     }
+    if env.block.time > round.end_time.plus_seconds(config.buffer_seconds) {
+        return Err(ContractError::EndWindowExpired {});
+    }
 
-    ROUNDS.update(
-        deps.storage,
-        config.current_round_id,
-        |round| -> StdResult<_> {
-            let mut r: Round = round.ok_or(StdError::generic_err("Round not found"))?;
-            r.end_price = price;
-            if r.lock_price > r.end_price {
-                r.result = Some(Direction::Down);
-            } else if r.lock_price > r.end_price {
-                r.result = Some(Direction::Up);
-            } else {
-                r.result = None
-            }
-            r.executed = true;
-            Ok(r)
-        },
-    )?;
+    round.end_price = price;
+    if round.lock_price > round.end_price {
+        round.result = Some(Direction::Down);
+    } else if round.lock_price < round.end_price {
+        round.result = Some(Direction::Up);
+    } else {
+        round.result = None
+    }
 
-    let round: Round = ROUNDS.load(deps.storage, config.current_round_id)?;
-
-    for user in round.participants.iter() {
-        let user_bet: Bet = USER_BETS.load(deps.storage, (config.current_round_id, user))?;
-        let round: Round = ROUNDS.load(deps.storage, config.current_round_id)?;
-
-        // Update the user leaderboard details
-        LEADERBOARDENTRY.update(deps.storage, user, |board| -> StdResult<_> {
-            let mut b: LeaderboardEntry =
-                board.ok_or(StdError::generic_err("User data not found"))?;
-
-            // seed reward
-            let winning_pool: Uint128 = match round.result {
-                Some(Direction::Up) => round.bull_pool,
-                Some(Direction::Down) => round.bear_pool,
-                None => return Err(StdError::generic_err("Round not ended")),
-            };
-            let losing_pool: Uint128 = round.total_pool - winning_pool;
-            let reward: Uint128 = (user_bet.amount * losing_pool) / winning_pool + user_bet.amount;
-
-            if round.result == Some(Direction::Up) && user_bet.direction == Direction::Up {
-                b.amount_won += reward;
-                b.total_won += 1;
-            } else {
-                b.amount_lost += user_bet.amount;
-                b.total_lost += 1;
-            }
+    // A tie voids the round and every bettor gets their stake back
+    let status = match round.result {
+        Some(_) => RoundStatus::Settled,
+        None => RoundStatus::Refunding,
+    };
+    transition(&mut round, status)?;
+
+    // The treasury fee is taken once here, winners share what is left and the
+    // keeper that ended the round gets its cut of the fee
+    let mut keeper_reward = Uint128::zero();
+    if let Some(direction) = &round.result {
+        let fee: Uint128 = payout::treasury_fee(round.total_pool, config.treasury_fee)?;
+        keeper_reward = payout::bps_share(fee, config.keeper_reward)?;
+        round.reward_base = payout::side_pool(&round, direction);
+        round.reward_amount = payout::reward_amount(round.total_pool, config.treasury_fee)?;
+        ROUND_PAYOUTS.save(storage, round_id, &RoundPayout::default())?;
+
+        // A running season takes its share of what the keeper leaves
+        let season_share = season::take_fee_share(storage, &round, fee - keeper_reward)?;
+        TREASURY_BALANCE.update(storage, |mut treasury| -> StdResult<_> {
+            treasury.accrued += fee - keeper_reward - season_share;
+            Ok(treasury)
+        })?;
+    } else {
+        season::record_void(storage, &round)?;
+    }
+    ROUNDS.save(storage, round_id, &round)?;
 
-            Ok(b)
+    // Bets are settled lazily, on claim or with ProcessSettlement
+    Ok(keeper_reward)
+}
+
+/// Applies a settled round's outcome to one bet: credits the winnings and updates the
+/// user's leaderboard stats. Does nothing if the bet was already settled.
+fn settle_bet(
+    storage: &mut dyn Storage,
+    round: &Round,
+    user: &Addr,
+    bet: &mut Bet,
+) -> Result<(), ContractError> {
+    if bet.settled {
+        return Ok(());
+    }
+    let reward = bet_reward(round, bet)?;
+
+    // Credit the winner's claimable balance
+    if let Some(reward) = reward {
+        WINNINGS.update(storage, user, |balance| -> StdResult<_> {
+            Ok(balance.unwrap_or_default() + reward)
         })?;
     }
 
-    // Update the current round id
-    CONFIG.update(deps.storage, |mut a| -> StdResult<_> {
-        a.current_round_id += 1;
+    // Update the user leaderboard details
+    LEADERBOARDENTRY.update(storage, user, |board| -> StdResult<_> {
+        let mut b: LeaderboardEntry = board.ok_or(StdError::generic_err("User data not found"))?;
+        b.record_outcome(round, bet)?;
+        Ok(b)
+    })?;
+    season::record_outcome(storage, round, user, bet)?;
+    record_payout(storage, round, reward.unwrap_or_default())?;
+
+    bet.settled = true;
+    USER_BETS.save(storage, (round.id, user), bet)?;
+    Ok(())
+}
+
+/// Counts a settled bet's reward against its round. Once the last bet is settled the
+/// rounding dust left of the reward amount goes to the treasury. Rounds settled before
+/// payouts were tracked are skipped.
+fn record_payout(storage: &mut dyn Storage, round: &Round, reward: Uint128) -> StdResult<()> {
+    let Some(mut progress) = ROUND_PAYOUTS.may_load(storage, round.id)? else {
+        return Ok(());
+    };
+    progress.paid = progress.paid.checked_add(reward)?;
+    progress.settled_bets += 1;
+    if progress.settled_bets < round.participant_count {
+        return ROUND_PAYOUTS.save(storage, round.id, &progress);
+    }
 
-        Ok(a)
+    let dust = payout::dust(round.reward_amount, progress.paid)?;
+    TREASURY_BALANCE.update(storage, |mut treasury| -> StdResult<_> {
+        treasury.accrued = treasury.accrued.checked_add(dust)?;
+        Ok(treasury)
     })?;
+    ROUND_PAYOUTS.remove(storage, round.id);
+    Ok(())
+}
+
+/// Settles up to `limit` bets of a settled round, resuming after the last bettor
+/// processed by the previous call. Anyone can call it.
+fn execute_process_settlement(
+    deps: DepsMut,
+    round_id: u64,
+    limit: u32,
+) -> Result<Response, ContractError> {
+    let round: Round = ROUNDS
+        .may_load(deps.storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
+    if round.status != RoundStatus::Settled {
+        return Err(ContractError::RoundNotEnded {});
+    }
+
+    let cursor: Option<Addr> = SETTLEMENT_CURSOR.may_load(deps.storage, round_id)?;
+    let bets: Vec<(Addr, Bet)> = USER_BETS
+        .prefix(round_id)
+        .range(
+            deps.storage,
+            cursor.as_ref().map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit as usize)
+        .collect::<StdResult<_>>()?;
+
+    // Bets settled by a claim in the meantime are skipped by settle_bet
+    for (user, mut bet) in bets.iter().cloned() {
+        settle_bet(deps.storage, &round, &user, &mut bet)?;
+    }
+    if let Some((last, _)) = bets.last() {
+        SETTLEMENT_CURSOR.save(deps.storage, round_id, last)?;
+    }
+    let done = (bets.len() as u32) < limit;
+
+    Ok(Response::new()
+        .add_attribute("action", "process_settlement")
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("processed", bets.len().to_string())
+        .add_attribute("done", done.to_string()))
+}
+
+/// Moves `round` to `to`, rejecting any transition the round lifecycle does not allow
+fn transition(round: &mut Round, to: RoundStatus) -> Result<(), ContractError> {
+    let allowed = matches!(
+        (&round.status, &to),
+        (RoundStatus::Pending, RoundStatus::Open)
+            | (RoundStatus::Open, RoundStatus::Locked)
+            | (RoundStatus::Open, RoundStatus::Cancelled)
+            | (RoundStatus::Locked, RoundStatus::Settled)
+            | (RoundStatus::Locked, RoundStatus::Refunding)
+            | (RoundStatus::Locked, RoundStatus::Cancelled)
+    );
+    if !allowed {
+        return Err(ContractError::InvalidRoundTransition {
+            from: round.status.clone(),
+            to,
+        });
+    }
+
+    round.status = to;
+    Ok(())
+}
+
+fn execute_cancel_round(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    round_id: u64,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let mut round: Round = ROUNDS
+        .may_load(deps.storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
+
+    // Anyone may void a round the operator failed to lock or end in time
+    let expired = has_expired(&round, &config, &env);
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.operator && !expired {
+        return Err(ContractError::UnauthorizedOperator {});
+    }
+
+    transition(&mut round, RoundStatus::Cancelled)?;
+    ROUNDS.save(deps.storage, round_id, &round)?;
+    season::record_void(deps.storage, &round)?;
 
     Ok(Response::new()
-        .add_attribute("action", "end_round")
-        .add_attribute("round-id", config.current_round_id.to_string()))
+        .add_attribute("action", "cancel_round")
+        .add_attribute("round_id", round_id.to_string()))
 }
 
 fn execute_pause(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
     let mut config: Config = CONFIG.load(deps.storage)?;
 
-    // Check if user is admin
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
+    // Check if user is pauser
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.pauser {
+        return Err(ContractError::UnauthorizedPauser {});
     }
 
     // pasue the contract
@@ -217,44 +602,213 @@ fn execute_pause(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response
 fn execute_resume(deps: DepsMut, _env: Env, info: MessageInfo) -> Result<Response, ContractError> {
     let mut config: Config = CONFIG.load(deps.storage)?;
 
-    // check if user is admin
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
+    // check if user is pauser
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.pauser {
+        return Err(ContractError::UnauthorizedPauser {});
     }
 
-    // resume the contract
+    // resume the contract, ExecuteRound picks the pipeline up again. If a window was
+    // missed meanwhile, GenesisStartRound restarts it.
     config.paused = false;
     CONFIG.save(deps.storage, &config)?;
 
     Ok(Response::new().add_attribute("action", "resume"))
 }
-fn execute_withdraw(
+fn execute_update_config(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    amount: Uint128,
-    recipient: String,
+    update: UpdateConfigMsg,
 ) -> Result<Response, ContractError> {
-    let config: Config = CONFIG.load(deps.storage)?;
+    let mut config: Config = CONFIG.load(deps.storage)?;
+
+    // Validate owner
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.owner {
+        return Err(ContractError::UnauthorizedOwner {});
+    }
+
+    // Only rounds started after this update pick up the new intervals
+    if let Some(betting_interval) = update.betting_interval {
+        config.betting_interval = betting_interval;
+    }
+    if let Some(live_interval) = update.live_interval {
+        config.live_interval = live_interval;
+    }
+    if let Some(buffer_seconds) = update.buffer_seconds {
+        config.buffer_seconds = buffer_seconds;
+    }
+    if let Some(oracle) = update.oracle {
+        config.oracle = oracle.validate(deps.api)?;
+    }
+    if let Some(keeper_reward) = update.keeper_reward {
+        if keeper_reward > 10_000 {
+            return Err(ContractError::InvalidKeeperReward {});
+        }
+        config.keeper_reward = keeper_reward;
+    }
+    if let Some(min_bet) = update.min_bet {
+        config.min_bet = min_bet;
+    }
+    if let Some(max_bet) = update.max_bet {
+        config.max_bet = Some(max_bet).filter(|max| !max.is_zero());
+    }
+    if let Some(max_user_stake) = update.max_user_stake {
+        config.max_user_stake = Some(max_user_stake).filter(|cap| !cap.is_zero());
+    }
+    if let Some(max_round_pool) = update.max_round_pool {
+        config.max_round_pool = Some(max_round_pool).filter(|cap| !cap.is_zero());
+    }
+    if let Some(cancel_fee) = update.cancel_fee {
+        if cancel_fee > 10_000 {
+            return Err(ContractError::InvalidCancelFee {});
+        }
+        config.cancel_fee = cancel_fee;
+    }
+
+    if !config.has_valid_timing() {
+        return Err(ContractError::InvalidRoundTiming {});
+    }
+    if !config.has_valid_bet_limits() {
+        return Err(ContractError::InvalidBetLimits {});
+    }
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_attribute("betting_interval", config.betting_interval.to_string())
+        .add_attribute("live_interval", config.live_interval.to_string())
+        .add_attribute("buffer_seconds", config.buffer_seconds.to_string())
+        .add_attribute("keeper_reward", config.keeper_reward.to_string()))
+}
+
+fn execute_update_roles(
+    deps: DepsMut,
+    info: MessageInfo,
+    operator: Option<String>,
+    pauser: Option<String>,
+    treasury: Option<String>,
+) -> Result<Response, ContractError> {
+    let mut roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.owner {
+        return Err(ContractError::UnauthorizedOwner {});
+    }
+
+    if let Some(operator) = operator {
+        roles.operator = deps.api.addr_validate(&operator)?;
+    }
+    if let Some(pauser) = pauser {
+        roles.pauser = deps.api.addr_validate(&pauser)?;
+    }
+    if let Some(treasury) = treasury {
+        roles.treasury = deps.api.addr_validate(&treasury)?;
+    }
+    ROLES.save(deps.storage, &roles)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_roles")
+        .add_attribute("operator", roles.operator)
+        .add_attribute("pauser", roles.pauser)
+        .add_attribute("treasury", roles.treasury))
+}
 
-    // Validate admin
-    if info.sender != config.admin {
-        return Err(ContractError::Unauthorized {});
+fn execute_transfer_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_owner: String,
+    expires_in: u64,
+) -> Result<Response, ContractError> {
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.owner {
+        return Err(ContractError::UnauthorizedOwner {});
     }
 
-    let bank_msg = BankMsg::Send {
-        to_address: recipient,
-        amount: vec![Coin {
-            denom: "uzig".to_string(),
-            amount,
-        }],
+    // A new proposal replaces any pending one
+    let pending = PendingOwner {
+        owner: deps.api.addr_validate(&new_owner)?,
+        expires_at: env.block.time.plus_seconds(expires_in),
     };
+    PENDING_OWNER.save(deps.storage, &pending)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer_ownership")
+        .add_attribute("pending_owner", pending.owner)
+        .add_attribute("expires_at", pending.expires_at.seconds().to_string()))
+}
+
+fn execute_accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let pending: PendingOwner = PENDING_OWNER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoPendingOwner {})?;
+    if info.sender != pending.owner {
+        return Err(ContractError::UnauthorizedPendingOwner {});
+    }
+    if env.block.time > pending.expires_at {
+        return Err(ContractError::OwnershipTransferExpired {});
+    }
+
+    let mut roles: Roles = ROLES.load(deps.storage)?;
+    let previous_owner = roles.owner;
+    roles.owner = pending.owner;
+    ROLES.save(deps.storage, &roles)?;
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("action", "accept_ownership")
+        .add_attribute("previous_owner", previous_owner)
+        .add_attribute("owner", roles.owner))
+}
+
+fn execute_cancel_ownership_transfer(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.owner {
+        return Err(ContractError::UnauthorizedOwner {});
+    }
+    if !PENDING_OWNER.exists(deps.storage) {
+        return Err(ContractError::NoPendingOwner {});
+    }
+    PENDING_OWNER.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "cancel_ownership_transfer"))
+}
+fn execute_claim_treasury(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let roles: Roles = ROLES.load(deps.storage)?;
+
+    // Either the owner or the treasury can trigger the payout
+    if info.sender != roles.owner && info.sender != roles.treasury {
+        return Err(ContractError::UnauthorizedTreasury {});
+    }
+
+    // Only fees accrued at settlement can leave the contract, never users' funds
+    let mut treasury: TreasuryBalance = TREASURY_BALANCE.load(deps.storage)?;
+    let amount: Uint128 = treasury.available();
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    treasury.withdrawn += amount;
+    TREASURY_BALANCE.save(deps.storage, &treasury)?;
+
+    let payout = config.stake_asset.transfer_msg(&roles.treasury, amount)?;
 
     Ok(Response::new()
-        .add_attribute("action", "withdraw")
+        .add_attribute("action", "claim_treasury")
         .add_attribute("amount", amount)
-        .add_attribute("recipient", info.sender)
-        .add_message(bank_msg))
+        .add_attribute("recipient", roles.treasury)
+        .add_message(payout))
 }
 
 // USER EXECUTE FUNCTION
@@ -264,6 +818,64 @@ fn execute_place_bet(
     info: MessageInfo,
     round_id: u64,
     direction: Direction,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let bet_amount = native_stake(&config, &info.funds)?;
+
+    place_bet(deps, env, info.sender, round_id, direction, bet_amount)
+}
+
+/// Amount of a native stake asset sent as exactly one coin of the market denom
+pub(crate) fn native_stake(config: &Config, funds: &[Coin]) -> Result<Uint128, ContractError> {
+    let denom = match &config.stake_asset {
+        StakeAsset::Native { denom } => denom,
+        StakeAsset::Cw20 { .. } => return Err(ContractError::WrongStakeAsset {}),
+    };
+
+    match funds {
+        [] => Err(ContractError::NoFundSent {}),
+        [coin] if &coin.denom != denom => Err(ContractError::InvalidDenom {
+            expected: denom.clone(),
+            received: coin.denom.clone(),
+        }),
+        [coin] => Ok(coin.amount),
+        _ => Err(ContractError::MixedDenoms {}),
+    }
+}
+
+fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    wrapper: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    // Only the configured token contract can deliver stakes
+    let config = CONFIG.load(deps.storage)?;
+    match &config.stake_asset {
+        StakeAsset::Cw20 { contract_addr } if *contract_addr == info.sender => {}
+        _ => return Err(ContractError::WrongStakeAsset {}),
+    }
+
+    let sender: Addr = deps.api.addr_validate(&wrapper.sender)?;
+    match from_json(&wrapper.msg)? {
+        ReceiveMsg::PlaceBet {
+            round_id,
+            direction,
+        } => place_bet(deps, env, sender, round_id, direction, wrapper.amount),
+        ReceiveMsg::FundSeason { season_id } => {
+            season::fund_season(deps, env, sender, season_id, wrapper.amount)
+        }
+    }
+}
+
+/// Records a bet of `bet_amount` already received from `bettor`
+fn place_bet(
+    deps: DepsMut,
+    env: Env,
+    bettor: Addr,
+    round_id: u64,
+    direction: Direction,
+    bet_amount: Uint128,
 ) -> Result<Response, ContractError> {
     // Acess the storage
     let config = CONFIG.load(deps.storage)?;
@@ -275,6 +887,12 @@ fn execute_place_bet(
     }
 
     // Check if the current round is active
+    if round.status == RoundStatus::Pending {
+        return Err(ContractError::RoundNotStarted {});
+    }
+    if round.status != RoundStatus::Open {
+        return Err(ContractError::RoundLocked {});
+    }
     let current_time = env.block.time;
     if current_time < round.start_time {
         return Err(ContractError::RoundNotStarted {});
@@ -284,47 +902,52 @@ fn execute_place_bet(
     }
 
     // check if user leaderboard exists
-    if LEADERBOARDENTRY
-        .may_load(deps.storage, &info.sender)?
-        .is_none()
-    {
-        let board: LeaderboardEntry = LeaderboardEntry {
-            user: info.sender.clone(),
-            total_amount_played: Uint128::zero(),
-            total_won: 0,
-            total_lost: 0,
-            amount_lost: Uint128::zero(),
-            amount_won: Uint128::zero(),
-            total_up: 0,
-            total_down: 0,
-        };
-        LEADERBOARDENTRY.save(deps.storage, &info.sender, &board)?;
-    }
-
-    // Check if the user already placed a bet
-    let user_address = info.sender.clone();
-    let user_bet_key = (round_id, &user_address);
-    if USER_BETS.may_load(deps.storage, user_bet_key)?.is_some() {
-        return Err(ContractError::BetAlreadyPlaced {});
-    }
-
-    // Check if user sent token
-    let bet_amount = info
-        .funds
-        .iter()
-        .find(|c| c.denom == "uzig")
-        .map(|c| c.amount)
-        .unwrap_or_else(Uint128::zero);
+    if LEADERBOARDENTRY.may_load(deps.storage, &bettor)?.is_none() {
+        let board: LeaderboardEntry = LeaderboardEntry::new(bettor.clone());
+        LEADERBOARDENTRY.save(deps.storage, &bettor, &board)?;
+    }
 
     if bet_amount.is_zero() {
         return Err(ContractError::NoFundSent {});
     }
+    if bet_amount < config.min_bet {
+        return Err(ContractError::BetBelowMinimum {
+            min: config.min_bet,
+        });
+    }
+    if let Some(max) = config.max_bet.filter(|max| bet_amount > *max) {
+        return Err(ContractError::BetAboveMaximum { max });
+    }
+    if let Some(cap) = config
+        .max_round_pool
+        .filter(|cap| round.total_pool + bet_amount > *cap)
+    {
+        return Err(ContractError::RoundPoolCapExceeded { cap });
+    }
 
-    // Save the user bet details
-    let user_bet = Bet {
-        amount: bet_amount,
-        direction: direction.clone(),
+    // A second bet tops up the user's position, it cannot take the other side
+    let user_address = bettor.clone();
+    let previous: Option<Bet> = USER_BETS.may_load(deps.storage, (round_id, &user_address))?;
+    let user_bet = match &previous {
+        Some(bet) if bet.direction != direction => {
+            return Err(ContractError::BetDirectionMismatch {})
+        }
+        Some(bet) => Bet {
+            amount: bet.amount + bet_amount,
+            ..bet.clone()
+        },
+        None => Bet {
+            amount: bet_amount,
+            direction: direction.clone(),
+            claimed: false,
+            settled: false,
+        },
     };
+    if let Some(cap) = config.max_user_stake.filter(|cap| user_bet.amount > *cap) {
+        return Err(ContractError::UserStakeCapExceeded { cap });
+    }
+
+    // Save the user bet details
     USER_BETS.save(deps.storage, (round_id, &user_address), &user_bet)?;
 
     // Update the pool
@@ -338,19 +961,18 @@ fn execute_place_bet(
     }
 
     round.total_pool += bet_amount;
-    round.participants.push(user_address);
 
-    // leaderboard entry
-    let mut board: LeaderboardEntry = LEADERBOARDENTRY.load(deps.storage, &info.sender)?;
-    board.total_amount_played += bet_amount;
-    match direction {
-        Direction::Down => {
-            board.total_down += 1;
-        }
-        Direction::Up => {
-            board.total_up += 1;
-        }
+    // leaderboard entry, a top-up only adds volume
+    let mut board: LeaderboardEntry = LEADERBOARDENTRY.load(deps.storage, &bettor)?;
+    if previous.is_some() {
+        board.record_top_up(bet_amount);
+        season::record_top_up(deps.storage, &round, &bettor, bet_amount)?;
+    } else {
+        round.participant_count += 1;
+        board.record_bet(&user_bet);
+        season::record_bet(deps.storage, &round, &bettor, &user_bet)?;
     }
+    LEADERBOARDENTRY.save(deps.storage, &bettor, &board)?;
 
     // Save round
     ROUNDS.save(deps.storage, round_id, &round)?;
@@ -358,7 +980,7 @@ fn execute_place_bet(
     Ok(Response::new()
         .add_attribute("action", "place_bet")
         .add_attribute("round_id", round_id.to_string())
-        .add_attribute("user", info.sender.to_string())
+        .add_attribute("user", bettor.to_string())
         .add_attribute("amount", bet_amount)
         .add_attribute(
             "direction",
@@ -366,63 +988,230 @@ fn execute_place_bet(
                 Direction::Down => "Down",
                 Direction::Up => "Up",
             },
-        ))
+        )
+        .add_attribute("position", user_bet.amount))
+}
+/// Takes the caller's bet out of an open round and refunds it, the cancellation fee goes
+/// to the treasury
+fn execute_cancel_bet(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    round_id: u64,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let mut round: Round = ROUNDS
+        .may_load(deps.storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
+    if config.paused {
+        return Err(ContractError::ContractPaused {});
+    }
+    if round.status != RoundStatus::Open || env.block.time >= round.lock_time {
+        return Err(ContractError::RoundLocked {});
+    }
+
+    let bet: Bet = USER_BETS
+        .may_load(deps.storage, (round_id, &info.sender))?
+        .ok_or(ContractError::BetNotFound {})?;
+    USER_BETS.remove(deps.storage, (round_id, &info.sender))?;
+
+    match bet.direction {
+        Direction::Up => round.bull_pool -= bet.amount,
+        Direction::Down => round.bear_pool -= bet.amount,
+    }
+    round.total_pool -= bet.amount;
+    round.participant_count -= 1;
+    ROUNDS.save(deps.storage, round_id, &round)?;
+
+    let mut board: LeaderboardEntry = LEADERBOARDENTRY.load(deps.storage, &info.sender)?;
+    board.remove_bet(&bet);
+    LEADERBOARDENTRY.save(deps.storage, &info.sender, &board)?;
+    season::record_cancel(deps.storage, &round, &info.sender, &bet)?;
+
+    let fee: Uint128 = payout::bps_share(bet.amount, config.cancel_fee)?;
+    let refund: Uint128 = bet.amount - fee;
+    TREASURY_BALANCE.update(deps.storage, |mut treasury| -> StdResult<_> {
+        treasury.accrued += fee;
+        Ok(treasury)
+    })?;
+
+    let mut response = Response::new()
+        .add_attribute("action", "cancel_bet")
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("user", info.sender.to_string())
+        .add_attribute("refund", refund)
+        .add_attribute("fee", fee);
+    if !refund.is_zero() {
+        response = response.add_message(config.stake_asset.transfer_msg(&info.sender, refund)?);
+    }
+
+    Ok(response)
 }
+
 fn execute_claim_reward(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
     round_id: u64,
 ) -> Result<Response, ContractError> {
-    let round: Round = ROUNDS.load(deps.storage, round_id)?;
     let config: Config = CONFIG.load(deps.storage)?;
+    let reward: Uint128 = claim_round(deps.storage, &info.sender, round_id)?;
+
+    // The reward is paid out of the ledger, with anything else credited so far
+    let amount: Uint128 = take_winnings(deps.storage, &info.sender)?;
+    if amount.is_zero() {
+        return Err(ContractError::InsufficientWinnings {});
+    }
+    let user_reward = config.stake_asset.transfer_msg(&info.sender, amount)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "claim_reward")
+        .add_attribute("round_id", round_id.to_string())
+        .add_attribute("user", info.sender.to_string())
+        .add_attribute("reward", reward)
+        .add_attribute("amount", amount)
+        .add_message(user_reward))
+}
+
+fn execute_claim_rewards(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    round_ids: Vec<u64>,
+    strict: bool,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let mut response = Response::new()
+        .add_attribute("action", "claim_rewards")
+        .add_attribute("user", info.sender.to_string());
+
+    // Strict mode fails the whole batch on the first unclaimable round,
+    // otherwise such rounds are skipped and reported
+    let mut total: Uint128 = Uint128::zero();
+    for round_id in round_ids {
+        match claim_round(deps.storage, &info.sender, round_id) {
+            Ok(reward) => {
+                total += reward;
+                response = response
+                    .add_attribute("round_id", round_id.to_string())
+                    .add_attribute("reward", reward);
+            }
+            Err(err) if !strict => {
+                response = response
+                    .add_attribute("skipped_round_id", round_id.to_string())
+                    .add_attribute("reason", err.to_string());
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    // The rewards are paid out of the ledger in one transfer
+    let amount: Uint128 = take_winnings(deps.storage, &info.sender)?;
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    let user_reward = config.stake_asset.transfer_msg(&info.sender, amount)?;
+
+    Ok(response
+        .add_attribute("total_reward", total)
+        .add_attribute("amount", amount)
+        .add_message(user_reward))
+}
+
+/// Validates a reward claim for `user` on `round_id` and marks the bet as claimed. The bet
+/// is settled first if that has not happened yet, which credits its reward to the ledger
+/// the caller pays out. A failed claim writes nothing else.
+fn claim_round(
+    storage: &mut dyn Storage,
+    user: &Addr,
+    round_id: u64,
+) -> Result<Uint128, ContractError> {
+    let round: Round = ROUNDS
+        .may_load(storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
 
     // check if round is still active
-    if !round.executed {
+    if round.is_voided() {
+        return Err(ContractError::RoundVoided {});
+    }
+    if round.status != RoundStatus::Settled {
         return Err(ContractError::RoundNotEnded {});
     }
 
-    let bet = USER_BETS
-        .may_load(deps.storage, (round_id, &info.sender))?
+    let mut bet = USER_BETS
+        .may_load(storage, (round_id, user))?
         .ok_or(ContractError::BetNotFound {})?;
+    if bet.claimed {
+        return Err(ContractError::AlreadyClaimed {});
+    }
+    settle_bet(storage, &round, user, &mut bet)?;
+    let reward: Uint128 = bet_reward(&round, &bet)?.ok_or(ContractError::BetLost {})?;
 
-    // get user bet and sned reward
-    let winning_pool: Uint128 = match round.result {
-        Some(Direction::Up) => round.bull_pool,
-        Some(Direction::Down) => round.bear_pool,
-        None => return Err(ContractError::RoundNotEnded {}),
-    };
-    let losing_pool: Uint128 = round.total_pool - winning_pool;
-    let reward: Uint128 = (bet.amount * losing_pool) / winning_pool + bet.amount;
+    bet.claimed = true;
+    USER_BETS.save(storage, (round_id, user), &bet)?;
 
-    // send feee to treasury
-    let percentage: u64 = config.treasury_fee / 100;
-    let fee: Uint128 = reward * Uint128::new(percentage.into());
-    let t_fee: Uint128 = reward - fee;
+    Ok(reward)
+}
+fn execute_claim_refund(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    round_id: u64,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let round: Round = ROUNDS
+        .may_load(deps.storage, round_id)?
+        .ok_or(ContractError::RoundNotFound {})?;
 
-    let treasury_fee = BankMsg::Send {
-        to_address: config.admin.to_string(),
-        amount: vec![Coin {
-            denom: "uzig".to_string(),
+    // Only cancelled or tied rounds are refunded
+    if !round.is_voided() {
+        return Err(ContractError::RoundNotRefundable {});
+    }
 
-            amount: t_fee,
-        }],
-    };
+    let mut bet = USER_BETS
+        .may_load(deps.storage, (round_id, &info.sender))?
+        .ok_or(ContractError::BetNotFound {})?;
+    if bet.claimed {
+        return Err(ContractError::AlreadyClaimed {});
+    }
+    bet.claimed = true;
+    USER_BETS.save(deps.storage, (round_id, &info.sender), &bet)?;
 
-    // Send the user reward
-    let user_reward = BankMsg::Send {
-        to_address: info.sender.to_string(),
-        amount: vec![Coin {
-            denom: "uzig".to_string(),
-            amount: reward,
-        }],
-    };
+    // The full stake is returned, no fee is taken
+    let refund = config.stake_asset.transfer_msg(&info.sender, bet.amount)?;
 
     Ok(Response::new()
-        .add_attribute("action", "claim_reward")
+        .add_attribute("action", "claim_refund")
         .add_attribute("round_id", round_id.to_string())
         .add_attribute("user", info.sender.to_string())
-        .add_attribute("reward", reward)
-        .add_message(user_reward)
-        .add_message(treasury_fee))
+        .add_attribute("refund", bet.amount)
+        .add_message(refund))
+}
+fn execute_withdraw_winnings(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let balance: Uint128 = take_winnings(deps.storage, &info.sender)?;
+    if balance.is_zero() {
+        return Err(ContractError::NoWinnings {});
+    }
+
+    // Pay out everything credited across all settled rounds at once
+    let payout = config.stake_asset.transfer_msg(&info.sender, balance)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_winnings")
+        .add_attribute("user", info.sender.to_string())
+        .add_attribute("amount", balance)
+        .add_message(payout))
+}
+
+/// Empties the winnings ledger of `user` and returns what it held. Every reward transfer
+/// goes through here, so a credit is paid out exactly once.
+fn take_winnings(storage: &mut dyn Storage, user: &Addr) -> StdResult<Uint128> {
+    let balance: Uint128 = WINNINGS.may_load(storage, user)?.unwrap_or_default();
+    WINNINGS.remove(storage, user);
+    Ok(balance)
 }
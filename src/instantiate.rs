@@ -1,6 +1,6 @@
 use cosmwasm_std::{DepsMut, Env, MessageInfo, Response, StdResult, StdError};
 use crate::msg::InstantiateMsg;
-use crate::state::{ CONFIG, Config};
+use crate::state::{ CONFIG, Config, ROLES, Roles, StakeAsset, TreasuryBalance, TREASURY_BALANCE};
 
 pub fn instantiate( 
     deps: DepsMut,
@@ -12,19 +12,87 @@ pub fn instantiate(
     if msg.treasury_fee > 10_000 {
         return  Err(StdError::generic_err("Treasury fee must be <= 10000 basis points"));
     }
+    if msg.keeper_reward > 10_000 {
+        return Err(StdError::generic_err("Keeper reward must be <= 10000 basis points"));
+    }
+    if msg.cancel_fee > 10_000 {
+        return Err(StdError::generic_err("Cancel fee must be <= 10000 basis points"));
+    }
+
+    let stake_asset = match msg.stake_asset {
+        StakeAsset::Native { denom } if denom.is_empty() => {
+            return Err(StdError::generic_err("Denom must not be empty"));
+        }
+        StakeAsset::Native { denom } => StakeAsset::Native { denom },
+        StakeAsset::Cw20 { contract_addr } => StakeAsset::Cw20 {
+            contract_addr: deps.api.addr_validate(contract_addr.as_str())?,
+        },
+    };
+
+    let role = |addr: Option<String>| match addr {
+        Some(addr) => deps.api.addr_validate(&addr),
+        None => Ok(info.sender.clone()),
+    };
+    let roles = Roles {
+        owner: info.sender.clone(),
+        operator: role(msg.operator)?,
+        pauser: role(msg.pauser)?,
+        treasury: role(msg.treasury)?,
+    };
 
     let config = Config {
-        admin: info.sender.clone(),
+        stake_asset: stake_asset.clone(),
+        oracle: msg.oracle.validate(deps.api)?,
         treasury_fee: msg.treasury_fee,
+        keeper_reward: msg.keeper_reward,
         paused: false,
-        current_round_id: 1
+        current_round_id: 0,
+        genesis_start_once: false,
+        genesis_lock_once: false,
+        betting_interval: msg.betting_interval,
+        live_interval: msg.live_interval,
+        buffer_seconds: msg.buffer_seconds,
+        min_bet: msg.min_bet,
+        // Zero means no limit, like in UpdateConfig
+        max_bet: msg.max_bet.filter(|max| !max.is_zero()),
+        max_user_stake: msg.max_user_stake.filter(|cap| !cap.is_zero()),
+        max_round_pool: msg.max_round_pool.filter(|cap| !cap.is_zero()),
+        cancel_fee: msg.cancel_fee,
     };
 
+    if !config.has_valid_timing() {
+        return Err(StdError::generic_err(
+            "Intervals must be non-zero, longer than the buffer and differ by at most the buffer",
+        ));
+    }
+
+    if !config.has_valid_bet_limits() {
+        return Err(StdError::generic_err(
+            "Minimum bet must not exceed the maximum bet, user stake or round pool",
+        ));
+    }
+
     CONFIG.save(deps.storage, &config)?;
+    ROLES.save(deps.storage, &roles)?;
+    TREASURY_BALANCE.save(deps.storage, &TreasuryBalance::default())?;
 
     Ok(Response::new()
         .add_attribute("action", "instantiate")
-        .add_attribute("admin", info.sender)
-        .add_attribute("treasury-fee", msg.treasury_fee.to_string()))
+        .add_attribute("owner", roles.owner)
+        .add_attribute("operator", roles.operator)
+        .add_attribute("pauser", roles.pauser)
+        .add_attribute("treasury", roles.treasury)
+        .add_attribute(
+            "stake-asset",
+            match stake_asset {
+                StakeAsset::Native { denom } => denom,
+                StakeAsset::Cw20 { contract_addr } => contract_addr.to_string(),
+            },
+        )
+        .add_attribute("treasury-fee", msg.treasury_fee.to_string())
+        .add_attribute("keeper-reward", msg.keeper_reward.to_string())
+        .add_attribute("betting-interval", msg.betting_interval.to_string())
+        .add_attribute("live-interval", msg.live_interval.to_string())
+        .add_attribute("buffer-seconds", msg.buffer_seconds.to_string()))
 
 }
\ No newline at end of file
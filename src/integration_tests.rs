@@ -0,0 +1,752 @@
+/// Minimal price-feed contract implementing `oracle::OracleQueryMsg` for multi-test
+pub mod mock_oracle {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{
+        to_json_binary, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdResult,
+    };
+    use cw_multi_test::{Contract, ContractWrapper};
+    use cw_storage_plus::Item;
+
+    use crate::oracle::{OracleQueryMsg, PriceResponse};
+
+    const PRICE: Item<PriceResponse> = Item::new("price");
+
+    #[cw_serde]
+    pub enum ExecuteMsg {
+        SetPrice(PriceResponse),
+    }
+
+    fn instantiate(
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: PriceResponse,
+    ) -> StdResult<Response> {
+        PRICE.save(deps.storage, &msg)?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> StdResult<Response> {
+        match msg {
+            ExecuteMsg::SetPrice(price) => PRICE.save(deps.storage, &price)?,
+        }
+        Ok(Response::new())
+    }
+
+    fn query(deps: Deps, _env: Env, msg: OracleQueryMsg) -> StdResult<Binary> {
+        match msg {
+            OracleQueryMsg::Price {} => to_json_binary(&PRICE.load(deps.storage)?),
+        }
+    }
+
+    pub fn contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(execute, instantiate, query))
+    }
+}
+
+/// Stand-in for a deployed 0.1.0 contract, instantiated straight into the 0.1.0 layout
+pub mod legacy_zigstake {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{
+        Addr, Binary, Deps, DepsMut, Empty, Env, MessageInfo, Response, StdError, StdResult,
+    };
+    use cw_multi_test::{Contract, ContractWrapper};
+
+    use crate::migrate::v0_1_0;
+
+    #[cw_serde]
+    pub struct InstantiateMsg {
+        pub contract_name: String,
+        pub version: String,
+        pub config: v0_1_0::Config,
+        pub rounds: Vec<v0_1_0::Round>,
+        pub bets: Vec<(u64, Addr, v0_1_0::Bet)>,
+    }
+
+    fn instantiate(
+        deps: DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: InstantiateMsg,
+    ) -> StdResult<Response> {
+        cw2::set_contract_version(deps.storage, msg.contract_name, msg.version)?;
+        v0_1_0::CONFIG.save(deps.storage, &msg.config)?;
+        for round in msg.rounds {
+            v0_1_0::ROUNDS.save(deps.storage, round.id, &round)?;
+        }
+        for (round_id, user, bet) in msg.bets {
+            v0_1_0::USER_BETS.save(deps.storage, (round_id, &user), &bet)?;
+        }
+        Ok(Response::new())
+    }
+
+    fn execute(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+        Err(StdError::generic_err("Not supported"))
+    }
+
+    fn query(_deps: Deps, _env: Env, _msg: Empty) -> StdResult<Binary> {
+        Err(StdError::generic_err("Not supported"))
+    }
+
+    pub fn contract() -> Box<dyn Contract<Empty>> {
+        Box::new(ContractWrapper::new(execute, instantiate, query))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coins, Addr, Coin, Empty, Int128, Timestamp, Uint128};
+    use cw_multi_test::error::AnyResult;
+    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
+
+    use super::{legacy_zigstake, mock_oracle};
+    use crate::migrate::v0_1_0;
+    use crate::msg::{
+        Direction, ExecuteMsg, InstantiateMsg, LeaderboardResponse, MigrateMsg,
+        ParticipantsResponse, QueryMsg, RankResponse, RolesResponse, RoundResponse, RoundStatus,
+        TreasuryResponse,
+    };
+    use crate::oracle::PriceResponse;
+    use crate::state::{Config, OracleSource, StakeAsset};
+    use crate::ContractError;
+
+    const DENOM: &str = "uzig";
+
+    fn contract_zigstake() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::contract::execute,
+            crate::contract::instantiate,
+            crate::contract::query,
+        )
+        .with_migrate(crate::contract::migrate);
+        Box::new(contract)
+    }
+
+    struct Suite {
+        app: App,
+        admin: Addr,
+        oracle: Addr,
+        zigstake: Addr,
+    }
+
+    impl Suite {
+        fn new() -> Self {
+            let mut app = AppBuilder::new().build(|router, api, storage| {
+                for user in ["alice", "bob", "keeper"] {
+                    router
+                        .bank
+                        .init_balance(storage, &api.addr_make(user), coins(10_000, DENOM))
+                        .unwrap();
+                }
+            });
+            let admin = app.api().addr_make("admin");
+            let now = app.block_info().time.seconds();
+
+            let oracle_code = app.store_code(mock_oracle::contract());
+            let oracle = app
+                .instantiate_contract(
+                    oracle_code,
+                    admin.clone(),
+                    &PriceResponse {
+                        price: Uint128::new(100),
+                        confidence: Uint128::new(1),
+                        publish_time: now,
+                    },
+                    &[],
+                    "oracle",
+                    None,
+                )
+                .unwrap();
+
+            let zigstake_code = app.store_code(contract_zigstake());
+            let zigstake = app
+                .instantiate_contract(
+                    zigstake_code,
+                    admin.clone(),
+                    &InstantiateMsg {
+                        stake_asset: StakeAsset::Native {
+                            denom: DENOM.to_string(),
+                        },
+                        oracle: OracleSource::Feed {
+                            contract_addr: oracle.clone(),
+                            max_age: 60,
+                            max_confidence_bps: 100,
+                        },
+                        treasury_fee: 200,
+                        operator: None,
+                        pauser: None,
+                        treasury: None,
+                        keeper_reward: 1_000,
+                        min_bet: Uint128::zero(),
+                        max_bet: None,
+                        max_user_stake: None,
+                        max_round_pool: None,
+                        cancel_fee: 0,
+                        betting_interval: 300,
+                        live_interval: 300,
+                        buffer_seconds: 30,
+                    },
+                    &[],
+                    "zigstake",
+                    None,
+                )
+                .unwrap();
+
+            Suite {
+                app,
+                admin,
+                oracle,
+                zigstake,
+            }
+        }
+
+        fn advance(&mut self, seconds: u64) {
+            self.app.update_block(|block| {
+                block.time = block.time.plus_seconds(seconds);
+                block.height += seconds / 5;
+            });
+        }
+
+        fn publish_price(&mut self, price: u128, confidence: u128, age: u64) {
+            let publish_time = self.app.block_info().time.seconds() - age;
+            let msg = mock_oracle::ExecuteMsg::SetPrice(PriceResponse {
+                price: Uint128::new(price),
+                confidence: Uint128::new(confidence),
+                publish_time,
+            });
+            self.app
+                .execute_contract(self.admin.clone(), self.oracle.clone(), &msg, &[])
+                .unwrap();
+        }
+
+        fn execute(&mut self, sender: &Addr, msg: ExecuteMsg, funds: &[Coin]) -> AnyResult<()> {
+            self.app
+                .execute_contract(sender.clone(), self.zigstake.clone(), &msg, funds)
+                .map(|_| ())
+        }
+
+        fn bet(&mut self, user: &str, round_id: u64, direction: Direction, amount: u128) {
+            let user = self.app.api().addr_make(user);
+            let msg = ExecuteMsg::PlaceBet {
+                round_id,
+                direction,
+            };
+            self.execute(&user, msg, &coins(amount, DENOM)).unwrap();
+        }
+
+        fn balance(&self, user: &str) -> Uint128 {
+            let user = self.app.api().addr_make(user);
+            self.app.wrap().query_balance(user, DENOM).unwrap().amount
+        }
+    }
+
+    #[test]
+    fn oracle_prices_settle_rounds() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+
+        suite
+            .execute(&admin, ExecuteMsg::GenesisStartRound {}, &[])
+            .unwrap();
+        suite.bet("alice", 1, Direction::Up, 1_000);
+        suite.bet("bob", 1, Direction::Down, 1_000);
+
+        suite.advance(300);
+        suite.publish_price(100, 1, 0);
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap();
+
+        // A price published when the rounds were due is still accepted a few seconds later
+        suite.advance(305);
+        suite.publish_price(120, 1, 5);
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::ExecuteRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap();
+
+        // Alice wins the 2000 pool minus the 2% treasury fee, the admin ending the
+        // round is paid the keeper share of that fee like anyone else
+        assert_eq!(
+            suite
+                .app
+                .wrap()
+                .query_balance(&admin, DENOM)
+                .unwrap()
+                .amount,
+            Uint128::new(4)
+        );
+        let alice = suite.app.api().addr_make("alice");
+        suite
+            .execute(&alice, ExecuteMsg::ClaimReward { round_id: 1 }, &[])
+            .unwrap();
+        assert_eq!(suite.balance("alice"), Uint128::new(10_960));
+        assert_eq!(suite.balance("bob"), Uint128::new(9_000));
+    }
+
+    #[test]
+    fn oracle_prices_are_checked() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+
+        suite
+            .execute(&admin, ExecuteMsg::GenesisStartRound {}, &[])
+            .unwrap();
+        suite.advance(300);
+
+        // The admin cannot override the feed
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: Some(Uint128::new(100)),
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::ManualPriceNotAllowed {}
+        ));
+
+        suite.publish_price(100, 1, 61);
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::StalePrice { age: 61 }
+        ));
+
+        // Recent enough, but published before round 1 was due to lock
+        suite.publish_price(100, 1, 1);
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::PriceBeforeRound {}
+        ));
+
+        // 2 is 2% of the price, above the 1% limit
+        suite.publish_price(100, 2, 0);
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::PriceConfidenceTooLow {}
+        ));
+
+        // A huge confidence interval is rejected rather than overflowing
+        suite.publish_price(100, u128::MAX, 0);
+        let err = suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::PriceConfidenceTooLow {}
+        ));
+
+        suite.publish_price(100, 1, 0);
+        suite
+            .execute(
+                &admin,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn keepers_progress_rounds() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let keeper = suite.app.api().addr_make("keeper");
+        let execute_round = ExecuteMsg::ExecuteRound {
+            price: None,
+            signed_price: None,
+        };
+
+        suite
+            .execute(&admin, ExecuteMsg::GenesisStartRound {}, &[])
+            .unwrap();
+        suite.bet("alice", 1, Direction::Up, 1_000);
+        suite.bet("bob", 1, Direction::Down, 1_000);
+
+        // Timing is still enforced for keepers, no feed price can be due before the lock
+        suite.advance(200);
+        suite.publish_price(100, 1, 0);
+        let err = suite
+            .execute(
+                &keeper,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::PriceBeforeRound {}
+        ));
+
+        suite.advance(100);
+        suite.publish_price(100, 1, 0);
+        suite
+            .execute(
+                &keeper,
+                ExecuteMsg::GenesisLockRound {
+                    price: None,
+                    signed_price: None,
+                },
+                &[],
+            )
+            .unwrap();
+
+        suite.advance(300);
+        suite.publish_price(120, 1, 0);
+        suite.execute(&keeper, execute_round, &[]).unwrap();
+
+        // 10% of the 40 fee goes to the keeper, the rest stays in the treasury
+        assert_eq!(suite.balance("keeper"), Uint128::new(10_004));
+        let treasury: TreasuryResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(&suite.zigstake, &QueryMsg::GetTreasury {})
+            .unwrap();
+        assert_eq!(treasury.accrued, Uint128::new(36));
+    }
+
+    fn legacy_round(id: u64, lock_price: u128, end_price: u128, executed: bool) -> v0_1_0::Round {
+        v0_1_0::Round {
+            id,
+            bull_pool: Uint128::zero(),
+            bear_pool: Uint128::zero(),
+            total_pool: Uint128::zero(),
+            start_time: Timestamp::from_seconds(id * 600),
+            lock_time: Timestamp::from_seconds(id * 600 + 300),
+            end_time: Timestamp::from_seconds(id * 600 + 300),
+            start_price: Uint128::new(100),
+            lock_price: Uint128::new(lock_price),
+            end_price: Uint128::new(end_price),
+            result: None,
+            executed,
+            participants: vec![],
+        }
+    }
+
+    /// Deploys a 0.1.0 contract with three rounds: an Up win that 0.1.0 recorded as a
+    /// tie, a real tie, and a round that was never ended
+    fn deploy_legacy(app: &mut App, admin: &Addr, contract_name: &str, version: &str) -> Addr {
+        deploy_legacy_with_fee(app, admin, contract_name, version, 200)
+    }
+
+    fn deploy_legacy_with_fee(
+        app: &mut App,
+        admin: &Addr,
+        contract_name: &str,
+        version: &str,
+        treasury_fee: u64,
+    ) -> Addr {
+        let alice = app.api().addr_make("alice");
+        let bob = app.api().addr_make("bob");
+        let mut rounds = vec![
+            legacy_round(1, 100, 120, true),
+            legacy_round(2, 100, 100, true),
+            legacy_round(3, 0, 0, false),
+        ];
+        let mut bets = vec![];
+        for (round, alice_bet, bob_bet) in [(0, 30, 10), (1, 10, 10), (2, 5, 5)] {
+            let round: &mut v0_1_0::Round = &mut rounds[round];
+            round.bull_pool = Uint128::new(alice_bet);
+            round.bear_pool = Uint128::new(bob_bet);
+            round.total_pool = Uint128::new(alice_bet + bob_bet);
+            round.participants = vec![alice.clone(), bob.clone()];
+            for (user, amount, direction) in [
+                (&alice, alice_bet, Direction::Up),
+                (&bob, bob_bet, Direction::Down),
+            ] {
+                let bet = v0_1_0::Bet {
+                    amount: Uint128::new(amount),
+                    direction,
+                };
+                bets.push((round.id, user.clone(), bet));
+            }
+        }
+
+        let code_id = app.store_code(legacy_zigstake::contract());
+        let msg = legacy_zigstake::InstantiateMsg {
+            contract_name: contract_name.to_string(),
+            version: version.to_string(),
+            config: v0_1_0::Config {
+                admin: admin.clone(),
+                paused: false,
+                treasury_fee,
+                current_round_id: 3,
+            },
+            rounds,
+            bets,
+        };
+        let legacy = app
+            .instantiate_contract(
+                code_id,
+                admin.clone(),
+                &msg,
+                &[],
+                "zigstake",
+                Some(admin.to_string()),
+            )
+            .unwrap();
+
+        // The contract holds every stake of the three rounds
+        app.send_tokens(alice, legacy.clone(), &coins(45, DENOM))
+            .unwrap();
+        app.send_tokens(bob, legacy.clone(), &coins(25, DENOM))
+            .unwrap();
+        legacy
+    }
+
+    #[test]
+    fn migrates_from_v0_1_0() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let legacy = deploy_legacy(&mut suite.app, &admin, "crates.io:zigstake", "0.1.0");
+        let code_id = suite.app.store_code(contract_zigstake());
+        suite
+            .app
+            .migrate_contract(admin.clone(), legacy.clone(), &MigrateMsg {}, code_id)
+            .unwrap();
+        suite.zigstake = legacy.clone();
+
+        let version = cw2::query_contract_info(&suite.app.wrap(), &legacy).unwrap();
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+        let round = |suite: &Suite, round_id| -> RoundResponse {
+            suite
+                .app
+                .wrap()
+                .query_wasm_smart(&legacy, &QueryMsg::GetRoundDetails { round_id })
+                .unwrap()
+        };
+        let round_1 = round(&suite, 1);
+        assert_eq!(round_1.status, RoundStatus::Settled);
+        assert_eq!(round_1.result, Some(Direction::Up));
+        assert_eq!(round_1.participant_count, 2);
+        let participants: ParticipantsResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &legacy,
+                &QueryMsg::GetRoundParticipants {
+                    round_id: 1,
+                    start_after: None,
+                    limit: None,
+                    order: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(participants.participants.len(), 2);
+        assert_eq!(round(&suite, 2).status, RoundStatus::Refunding);
+        assert_eq!(round(&suite, 3).status, RoundStatus::Cancelled);
+
+        // Alice wins the whole round 1 pool, bob is refunded the other two rounds
+        let alice = suite.app.api().addr_make("alice");
+        let bob = suite.app.api().addr_make("bob");
+        suite
+            .execute(&alice, ExecuteMsg::ClaimReward { round_id: 1 }, &[])
+            .unwrap();
+        for round_id in [2, 3] {
+            suite
+                .execute(&alice, ExecuteMsg::ClaimRefund { round_id }, &[])
+                .unwrap();
+            suite
+                .execute(&bob, ExecuteMsg::ClaimRefund { round_id }, &[])
+                .unwrap();
+        }
+        assert_eq!(suite.balance("alice"), Uint128::new(10_010));
+        assert_eq!(suite.balance("bob"), Uint128::new(9_990));
+
+        let leaderboard: LeaderboardResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &legacy,
+                &QueryMsg::GetLeaderboard {
+                    sort_by: None,
+                    limit: None,
+                    start_after: None,
+                },
+            )
+            .unwrap();
+        let alice_entry = leaderboard
+            .entries
+            .iter()
+            .find(|entry| entry.user == alice)
+            .unwrap();
+        assert_eq!(alice_entry.total_won, 1);
+        assert_eq!(alice_entry.amount_won, Uint128::new(40));
+        assert_eq!(alice_entry.total_amount_played, Uint128::new(45));
+        assert_eq!(alice_entry.net_profit, Int128::new(10));
+        let bob_rank: RankResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &legacy,
+                &QueryMsg::GetUserRank {
+                    user: bob.to_string(),
+                    sort_by: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(bob_rank.rank, Some(2));
+        assert_eq!(bob_rank.entry.unwrap().net_profit, Int128::new(-10));
+
+        // The admin holds every role and restarts the pipeline after the last round
+        let roles: RolesResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(&legacy, &QueryMsg::GetRoles {})
+            .unwrap();
+        assert_eq!(roles.operator, admin);
+        let config: Config = suite
+            .app
+            .wrap()
+            .query_wasm_smart(&legacy, &QueryMsg::GetConfig {})
+            .unwrap();
+        assert_eq!(config.min_bet, Uint128::zero());
+        assert_eq!((config.max_bet, config.max_round_pool), (None, None));
+        suite
+            .execute(&admin, ExecuteMsg::GenesisStartRound {}, &[])
+            .unwrap();
+        assert_eq!(round(&suite, 4).status, RoundStatus::Open);
+    }
+
+    #[test]
+    fn migrates_paid_claims_from_v0_1_0() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        // Below a 100 fee 0.1.0 paid every claim, alice may already hold her round 1 win
+        let legacy =
+            deploy_legacy_with_fee(&mut suite.app, &admin, "crates.io:zigstake", "0.1.0", 50);
+        let code_id = suite.app.store_code(contract_zigstake());
+        suite
+            .app
+            .migrate_contract(admin, legacy.clone(), &MigrateMsg {}, code_id)
+            .unwrap();
+        suite.zigstake = legacy.clone();
+
+        let alice = suite.app.api().addr_make("alice");
+        let err = suite
+            .execute(&alice, ExecuteMsg::ClaimReward { round_id: 1 }, &[])
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::AlreadyClaimed {}
+        ));
+        let err = suite
+            .execute(&alice, ExecuteMsg::WithdrawWinnings {}, &[])
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::NoWinnings {}
+        ));
+
+        // The win still counts on the leaderboard and the refunds are untouched
+        let rank: RankResponse = suite
+            .app
+            .wrap()
+            .query_wasm_smart(
+                &legacy,
+                &QueryMsg::GetUserRank {
+                    user: alice.to_string(),
+                    sort_by: None,
+                },
+            )
+            .unwrap();
+        let entry = rank.entry.unwrap();
+        assert_eq!((entry.total_won, entry.amount_won), (1, Uint128::new(40)));
+        suite
+            .execute(&alice, ExecuteMsg::ClaimRefund { round_id: 2 }, &[])
+            .unwrap();
+        assert_eq!(suite.balance("alice"), Uint128::new(9_965));
+    }
+
+    #[test]
+    fn migration_checks_name_and_version() {
+        let mut suite = Suite::new();
+        let admin = suite.admin.clone();
+        let code_id = suite.app.store_code(contract_zigstake());
+
+        let other = deploy_legacy(&mut suite.app, &admin, "crates.io:other", "0.1.0");
+        let err = suite
+            .app
+            .migrate_contract(admin.clone(), other, &MigrateMsg {}, code_id)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::InvalidContractName { .. }
+        ));
+
+        let newer = deploy_legacy(&mut suite.app, &admin, "crates.io:zigstake", "9.0.0");
+        let err = suite
+            .app
+            .migrate_contract(admin.clone(), newer, &MigrateMsg {}, code_id)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast().unwrap(),
+            ContractError::CannotMigrateDowngrade { .. }
+        ));
+    }
+}
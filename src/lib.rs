@@ -6,5 +6,12 @@ pub mod state;
 pub mod instantiate;
 pub mod execute;
 pub mod query;
+pub mod migrate;
+pub mod oracle;
+pub mod payout;
+pub mod season;
+
+#[cfg(test)]
+mod integration_tests;
 
 pub use crate::error::ContractError;
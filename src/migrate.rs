@@ -0,0 +1,650 @@
+use cosmwasm_std::{DepsMut, Env, Response, StdError};
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::msg::MigrateMsg;
+
+type MigrationStep = fn(DepsMut, &Env) -> Result<(), ContractError>;
+
+/// State layout changes in release order. A step runs when the stored version is older
+/// than the version it was released with, so upgrades can skip releases.
+const MIGRATIONS: &[(&str, MigrationStep)] = &[
+    ("0.2.0", to_v0_2_0::migrate),
+    ("0.3.0", to_v0_3_0::migrate),
+    ("0.4.0", to_v0_4_0::migrate),
+    ("0.5.0", to_v0_5_0::migrate),
+    ("0.6.0", to_v0_6_0::migrate),
+    ("0.7.0", to_v0_7_0::migrate),
+    ("0.8.0", to_v0_8_0::migrate),
+    ("0.9.0", to_v0_9_0::migrate),
+];
+
+/// Runs every migration step released after `from`. Name and downgrade checks are done
+/// by the entry point before this is called.
+pub fn migrate(
+    mut deps: DepsMut,
+    env: Env,
+    _msg: MigrateMsg,
+    from: &Version,
+) -> Result<Response, ContractError> {
+    let mut applied: Vec<&str> = vec![];
+    for (version, step) in MIGRATIONS {
+        let released =
+            Version::parse(version).map_err(|err| StdError::generic_err(err.to_string()))?;
+        if *from < released {
+            step(deps.branch(), &env)?;
+            applied.push(version);
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("steps", applied.join(",")))
+}
+
+/// State layout written by 0.1.0
+pub mod v0_1_0 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, Timestamp, Uint128};
+    use cw_storage_plus::{Item, Map};
+
+    use crate::msg::Direction;
+
+    #[cw_serde]
+    pub struct Config {
+        pub admin: Addr,
+        pub paused: bool,
+        pub treasury_fee: u64,
+        pub current_round_id: u64,
+    }
+
+    #[cw_serde]
+    pub struct Round {
+        pub id: u64,
+        pub bull_pool: Uint128,
+        pub bear_pool: Uint128,
+        pub total_pool: Uint128,
+        pub start_time: Timestamp,
+        pub lock_time: Timestamp,
+        pub end_time: Timestamp,
+        pub start_price: Uint128,
+        pub lock_price: Uint128,
+        pub end_price: Uint128,
+        pub result: Option<Direction>,
+        pub executed: bool,
+        pub participants: Vec<Addr>,
+    }
+
+    #[cw_serde]
+    pub struct Bet {
+        pub amount: Uint128,
+        pub direction: Direction,
+    }
+
+    pub const CONFIG: Item<Config> = Item::new("config");
+    pub const ROUNDS: Map<u64, Round> = Map::new("rounds");
+    pub const USER_BETS: Map<(u64, &Addr), Bet> = Map::new("user_bets");
+}
+
+/// State layout written by 0.2.0, where it differs from the current one
+pub mod v0_2_0 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, Timestamp, Uint128};
+    use cw_storage_plus::Map;
+
+    use crate::msg::{Direction, RoundStatus};
+
+    #[cw_serde]
+    pub struct Round {
+        pub id: u64,
+        pub bull_pool: Uint128,
+        pub bear_pool: Uint128,
+        pub total_pool: Uint128,
+        pub start_time: Timestamp,
+        pub lock_time: Timestamp,
+        pub end_time: Timestamp,
+        pub start_price: Uint128,
+        pub lock_price: Uint128,
+        pub end_price: Uint128,
+        pub result: Option<Direction>,
+        pub reward_base: Uint128,
+        pub reward_amount: Uint128,
+        pub status: RoundStatus,
+        pub participants: Vec<Addr>,
+    }
+
+    /// Also written by 0.3.0
+    #[cw_serde]
+    pub struct Bet {
+        pub amount: Uint128,
+        pub direction: Direction,
+        pub claimed: bool,
+    }
+
+    pub const ROUNDS: Map<u64, Round> = Map::new("rounds");
+    pub const USER_BETS: Map<(u64, &Addr), Bet> = Map::new("user_bets");
+}
+
+/// State layout written by 0.4.0, where it differs from the current one
+pub mod v0_4_0 {
+    use cosmwasm_std::Addr;
+    use cw_storage_plus::Map;
+
+    use crate::state::Bet;
+
+    /// Bets without the index by user
+    pub const USER_BETS: Map<(u64, &Addr), Bet> = Map::new("user_bets");
+}
+
+/// State layout written by 0.5.0, where it differs from the current one
+pub mod v0_5_0 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::{Addr, Uint128};
+    use cw_storage_plus::Map;
+
+    /// Also written by 0.1.0 to 0.4.0
+    #[cw_serde]
+    pub struct LeaderboardEntry {
+        pub user: Addr,
+        pub total_amount_played: Uint128,
+        pub total_won: u64,
+        pub total_lost: u64,
+        pub total_up: u64,
+        pub total_down: u64,
+        pub amount_won: Uint128,
+        pub amount_lost: Uint128,
+    }
+
+    /// Leaderboard without the net profit and the ranking indexes
+    pub const LEADERBOARD: Map<&Addr, LeaderboardEntry> = Map::new("leaderboard");
+}
+
+/// State layout written by 0.6.0, where it differs from the current one
+pub mod v0_6_0 {
+    use cosmwasm_schema::cw_serde;
+    use cw_storage_plus::Item;
+
+    use crate::state::{OracleSource, StakeAsset};
+
+    /// Also written by 0.2.0 to 0.5.0
+    #[cw_serde]
+    pub struct Config {
+        pub paused: bool,
+        pub stake_asset: StakeAsset,
+        pub oracle: OracleSource,
+        pub treasury_fee: u64,
+        pub keeper_reward: u64,
+        pub current_round_id: u64,
+        pub genesis_start_once: bool,
+        pub genesis_lock_once: bool,
+        pub betting_interval: u64,
+        pub live_interval: u64,
+        pub buffer_seconds: u64,
+    }
+
+    /// Config without the stake cap
+    pub const CONFIG: Item<Config> = Item::new("config");
+}
+
+/// State layout written by 0.7.0, where it differs from the current one
+pub mod v0_7_0 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::Uint128;
+    use cw_storage_plus::Item;
+
+    use crate::state::{OracleSource, StakeAsset};
+
+    #[cw_serde]
+    pub struct Config {
+        pub paused: bool,
+        pub stake_asset: StakeAsset,
+        pub oracle: OracleSource,
+        pub treasury_fee: u64,
+        pub keeper_reward: u64,
+        pub current_round_id: u64,
+        pub genesis_start_once: bool,
+        pub genesis_lock_once: bool,
+        pub betting_interval: u64,
+        pub live_interval: u64,
+        pub buffer_seconds: u64,
+        pub max_user_stake: Option<Uint128>,
+    }
+
+    /// Config without the cancellation fee
+    pub const CONFIG: Item<Config> = Item::new("config");
+}
+
+/// State layout written by 0.8.0, where it differs from the current one
+pub mod v0_8_0 {
+    use cosmwasm_schema::cw_serde;
+    use cosmwasm_std::Uint128;
+    use cw_storage_plus::Item;
+
+    use crate::state::{OracleSource, StakeAsset};
+
+    #[cw_serde]
+    pub struct Config {
+        pub paused: bool,
+        pub stake_asset: StakeAsset,
+        pub oracle: OracleSource,
+        pub treasury_fee: u64,
+        pub keeper_reward: u64,
+        pub current_round_id: u64,
+        pub genesis_start_once: bool,
+        pub genesis_lock_once: bool,
+        pub betting_interval: u64,
+        pub live_interval: u64,
+        pub buffer_seconds: u64,
+        pub max_user_stake: Option<Uint128>,
+        pub cancel_fee: u64,
+    }
+
+    /// Config without the bet limits and the round pool cap
+    pub const CONFIG: Item<Config> = Item::new("config");
+}
+
+/// 0.2.0 moved to the genesis round pipeline with round statuses, a winnings ledger,
+/// treasury accounting, configurable assets, oracles and roles.
+mod to_v0_2_0 {
+    use std::collections::BTreeMap;
+
+    use cosmwasm_std::{Addr, DepsMut, Env, Order, StdResult, Uint128};
+
+    use super::v0_1_0;
+    use super::v0_2_0::{Bet, Round, ROUNDS, USER_BETS};
+    use super::v0_5_0::{LeaderboardEntry, LEADERBOARD};
+    use super::v0_6_0::{Config, CONFIG};
+    use crate::error::ContractError;
+    use crate::msg::{Direction, RoundStatus};
+    use crate::state::{
+        OracleSource, Roles, StakeAsset, TreasuryBalance, ROLES, TREASURY_BALANCE, WINNINGS,
+    };
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let legacy: v0_1_0::Config = v0_1_0::CONFIG.load(deps.storage)?;
+        // Below a 100 fee 0.1.0 paid out every claim without recording it, so its winners
+        // may already be paid and cannot be credited again
+        let claims_paid = legacy.treasury_fee < 100;
+
+        // 0.1.0 only knew uzig and five minute rounds, the admin held every role
+        let rounds: Vec<v0_1_0::Round> = v0_1_0::ROUNDS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, round)| round))
+            .collect::<StdResult<_>>()?;
+        let config = Config {
+            paused: legacy.paused,
+            stake_asset: StakeAsset::Native {
+                denom: "uzig".to_string(),
+            },
+            oracle: OracleSource::Manual,
+            treasury_fee: legacy.treasury_fee,
+            keeper_reward: 0,
+            // The pipeline is bootstrapped again with GenesisStartRound after the last round
+            current_round_id: rounds.last().map_or(0, |round| round.id),
+            genesis_start_once: false,
+            genesis_lock_once: false,
+            betting_interval: 300,
+            live_interval: 300,
+            buffer_seconds: 30,
+        };
+        CONFIG.save(deps.storage, &config)?;
+        ROLES.save(
+            deps.storage,
+            &Roles {
+                owner: legacy.admin.clone(),
+                operator: legacy.admin.clone(),
+                pauser: legacy.admin.clone(),
+                treasury: legacy.admin,
+            },
+        )?;
+        TREASURY_BALANCE.save(deps.storage, &TreasuryBalance::default())?;
+
+        // Leaderboards are rebuilt from the bets, 0.1.0 lost most of the updates
+        let mut boards: BTreeMap<Addr, LeaderboardEntry> = BTreeMap::new();
+        for legacy_round in rounds {
+            let round = migrate_round(&legacy_round);
+            ROUNDS.save(deps.storage, round.id, &round)?;
+
+            for user in legacy_round.participants.iter() {
+                let legacy_bet = v0_1_0::USER_BETS.load(deps.storage, (round.id, user))?;
+                let won = round.status == RoundStatus::Settled
+                    && round.result.as_ref() == Some(&legacy_bet.direction);
+                let bet = Bet {
+                    amount: legacy_bet.amount,
+                    direction: legacy_bet.direction,
+                    claimed: won && claims_paid,
+                };
+                USER_BETS.save(deps.storage, (round.id, user), &bet)?;
+
+                let board = boards
+                    .entry(user.clone())
+                    .or_insert_with(|| new_entry(user));
+                board.total_amount_played += bet.amount;
+                match bet.direction {
+                    Direction::Up => board.total_up += 1,
+                    Direction::Down => board.total_down += 1,
+                }
+                if round.status != RoundStatus::Settled {
+                    continue;
+                }
+
+                // 0.1.0 kept no record of claims, winning bets it could not pay are
+                // credited once
+                if won {
+                    let reward = bet
+                        .amount
+                        .multiply_ratio(round.reward_amount, round.reward_base);
+                    if !claims_paid {
+                        WINNINGS.update(deps.storage, user, |balance| -> StdResult<_> {
+                            Ok(balance.unwrap_or_default() + reward)
+                        })?;
+                    }
+                    board.amount_won += reward;
+                    board.total_won += 1;
+                } else {
+                    board.amount_lost += bet.amount;
+                    board.total_lost += 1;
+                }
+            }
+        }
+        for (user, board) in boards {
+            LEADERBOARD.save(deps.storage, &user, &board)?;
+        }
+
+        Ok(())
+    }
+
+    /// Executed rounds are settled from their recorded prices, 0.1.0 could never resolve
+    /// Up. Rounds still running, or with an empty side, are cancelled so bettors get
+    /// refunded.
+    fn migrate_round(legacy: &v0_1_0::Round) -> Round {
+        let result = if legacy.lock_price > legacy.end_price {
+            Some(Direction::Down)
+        } else if legacy.lock_price < legacy.end_price {
+            Some(Direction::Up)
+        } else {
+            None
+        };
+        let one_sided = legacy.bull_pool.is_zero() || legacy.bear_pool.is_zero();
+        let status = match (&result, legacy.executed && !one_sided) {
+            (_, false) => RoundStatus::Cancelled,
+            (Some(_), true) => RoundStatus::Settled,
+            (None, true) => RoundStatus::Refunding,
+        };
+
+        // Fees were taken at claim time in 0.1.0, settled rounds pay out the whole pool
+        let (reward_base, reward_amount) = match (&status, &result) {
+            (RoundStatus::Settled, Some(Direction::Up)) => (legacy.bull_pool, legacy.total_pool),
+            (RoundStatus::Settled, Some(Direction::Down)) => (legacy.bear_pool, legacy.total_pool),
+            _ => (Uint128::zero(), Uint128::zero()),
+        };
+
+        Round {
+            id: legacy.id,
+            bull_pool: legacy.bull_pool,
+            bear_pool: legacy.bear_pool,
+            total_pool: legacy.total_pool,
+            start_time: legacy.start_time,
+            lock_time: legacy.lock_time,
+            end_time: legacy.end_time,
+            start_price: legacy.start_price,
+            lock_price: legacy.lock_price,
+            end_price: legacy.end_price,
+            result: if status == RoundStatus::Settled {
+                result
+            } else {
+                None
+            },
+            reward_base,
+            reward_amount,
+            status,
+            participants: legacy.participants.clone(),
+        }
+    }
+
+    fn new_entry(user: &Addr) -> LeaderboardEntry {
+        LeaderboardEntry {
+            user: user.clone(),
+            total_amount_played: Uint128::zero(),
+            total_won: 0,
+            total_lost: 0,
+            total_up: 0,
+            total_down: 0,
+            amount_won: Uint128::zero(),
+            amount_lost: Uint128::zero(),
+        }
+    }
+}
+
+/// 0.3.0 replaced the participant list of a round with a count, bettors are read from
+/// the bets keyed by round instead
+mod to_v0_3_0 {
+    use cosmwasm_std::{DepsMut, Env, Order, StdResult};
+
+    use super::v0_2_0;
+    use crate::error::ContractError;
+    use crate::state::{Round, ROUNDS};
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let rounds: Vec<v0_2_0::Round> = v0_2_0::ROUNDS
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, round)| round))
+            .collect::<StdResult<_>>()?;
+
+        for legacy in rounds {
+            let round = Round {
+                id: legacy.id,
+                bull_pool: legacy.bull_pool,
+                bear_pool: legacy.bear_pool,
+                total_pool: legacy.total_pool,
+                start_time: legacy.start_time,
+                lock_time: legacy.lock_time,
+                end_time: legacy.end_time,
+                start_price: legacy.start_price,
+                lock_price: legacy.lock_price,
+                end_price: legacy.end_price,
+                result: legacy.result,
+                reward_base: legacy.reward_base,
+                reward_amount: legacy.reward_amount,
+                status: legacy.status,
+                participant_count: legacy.participants.len() as u64,
+            };
+            ROUNDS.save(deps.storage, round.id, &round)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 0.4.0 settles bets lazily. Every bet of an ended round was already applied to the
+/// winnings and leaderboard at settlement, only bets of running rounds are left unsettled.
+mod to_v0_4_0 {
+    use cosmwasm_std::{Addr, DepsMut, Env, Order, StdResult};
+
+    use super::{v0_2_0, v0_4_0};
+    use crate::error::ContractError;
+    use crate::state::{Bet, Round, ROUNDS};
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let bets: Vec<((u64, Addr), v0_2_0::Bet)> = v0_2_0::USER_BETS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+
+        for ((round_id, user), legacy) in bets {
+            let round: Round = ROUNDS.load(deps.storage, round_id)?;
+            let bet = Bet {
+                amount: legacy.amount,
+                direction: legacy.direction,
+                claimed: legacy.claimed,
+                settled: round.is_ended(),
+            };
+            v0_4_0::USER_BETS.save(deps.storage, (round_id, &user), &bet)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 0.5.0 indexes bets by user, every bet is saved again to build the index
+mod to_v0_5_0 {
+    use cosmwasm_std::{Addr, DepsMut, Env, Order, StdResult};
+
+    use super::v0_4_0;
+    use crate::error::ContractError;
+    use crate::state::{Bet, USER_BETS};
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let bets: Vec<((u64, Addr), Bet)> = v0_4_0::USER_BETS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+
+        for ((round_id, user), bet) in bets {
+            USER_BETS.save(deps.storage, (round_id, &user), &bet)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 0.6.0 tracks the net profit of every user and ranks the leaderboard with secondary
+/// indexes. The net profit is rebuilt from the bets settled so far.
+mod to_v0_6_0 {
+    use std::collections::BTreeMap;
+
+    use cosmwasm_std::{Addr, DepsMut, Env, Int128, Order, StdResult};
+
+    use super::{v0_4_0, v0_5_0};
+    use crate::error::ContractError;
+    use crate::msg::{LeaderboardEntry, RoundStatus};
+    use crate::payout::bet_net_profit;
+    use crate::state::{Bet, Round, LEADERBOARDENTRY, ROUNDS};
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let bets: Vec<((u64, Addr), Bet)> = v0_4_0::USER_BETS
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        let mut net_profits: BTreeMap<Addr, Int128> = BTreeMap::new();
+        for ((round_id, user), bet) in bets {
+            let round: Round = ROUNDS.load(deps.storage, round_id)?;
+            if !bet.settled || round.status != RoundStatus::Settled {
+                continue;
+            }
+            *net_profits.entry(user).or_default() += bet_net_profit(&round, &bet)?;
+        }
+
+        let boards: Vec<(Addr, v0_5_0::LeaderboardEntry)> = v0_5_0::LEADERBOARD
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for (user, legacy) in boards {
+            // The plain entry is replaced in place, the indexes are written from scratch
+            v0_5_0::LEADERBOARD.remove(deps.storage, &user);
+            let board = LeaderboardEntry {
+                net_profit: net_profits.get(&user).copied().unwrap_or_default(),
+                user: legacy.user,
+                total_amount_played: legacy.total_amount_played,
+                total_won: legacy.total_won,
+                total_lost: legacy.total_lost,
+                total_up: legacy.total_up,
+                total_down: legacy.total_down,
+                amount_won: legacy.amount_won,
+                amount_lost: legacy.amount_lost,
+            };
+            LEADERBOARDENTRY.save(deps.storage, &user, &board)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 0.7.0 lets users top up their bets up to an optional cap, existing markets have none
+mod to_v0_7_0 {
+    use cosmwasm_std::{DepsMut, Env};
+
+    use super::{v0_6_0, v0_7_0};
+    use crate::error::ContractError;
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let legacy: v0_6_0::Config = v0_6_0::CONFIG.load(deps.storage)?;
+        let config = v0_7_0::Config {
+            paused: legacy.paused,
+            stake_asset: legacy.stake_asset,
+            oracle: legacy.oracle,
+            treasury_fee: legacy.treasury_fee,
+            keeper_reward: legacy.keeper_reward,
+            current_round_id: legacy.current_round_id,
+            genesis_start_once: legacy.genesis_start_once,
+            genesis_lock_once: legacy.genesis_lock_once,
+            betting_interval: legacy.betting_interval,
+            live_interval: legacy.live_interval,
+            buffer_seconds: legacy.buffer_seconds,
+            max_user_stake: None,
+        };
+        v0_7_0::CONFIG.save(deps.storage, &config)?;
+
+        Ok(())
+    }
+}
+
+/// 0.8.0 lets users cancel bets of open rounds, existing markets charge no fee for it
+mod to_v0_8_0 {
+    use cosmwasm_std::{DepsMut, Env};
+
+    use super::{v0_7_0, v0_8_0};
+    use crate::error::ContractError;
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let legacy: v0_7_0::Config = v0_7_0::CONFIG.load(deps.storage)?;
+        let config = v0_8_0::Config {
+            paused: legacy.paused,
+            stake_asset: legacy.stake_asset,
+            oracle: legacy.oracle,
+            treasury_fee: legacy.treasury_fee,
+            keeper_reward: legacy.keeper_reward,
+            current_round_id: legacy.current_round_id,
+            genesis_start_once: legacy.genesis_start_once,
+            genesis_lock_once: legacy.genesis_lock_once,
+            betting_interval: legacy.betting_interval,
+            live_interval: legacy.live_interval,
+            buffer_seconds: legacy.buffer_seconds,
+            max_user_stake: legacy.max_user_stake,
+            cancel_fee: 0,
+        };
+        v0_8_0::CONFIG.save(deps.storage, &config)?;
+
+        Ok(())
+    }
+}
+
+/// 0.9.0 bounds bet sizes and round pools, existing markets keep accepting any bet
+mod to_v0_9_0 {
+    use cosmwasm_std::{DepsMut, Env, Uint128};
+
+    use super::v0_8_0;
+    use crate::error::ContractError;
+    use crate::state::{Config, CONFIG};
+
+    pub fn migrate(deps: DepsMut, _env: &Env) -> Result<(), ContractError> {
+        let legacy: v0_8_0::Config = v0_8_0::CONFIG.load(deps.storage)?;
+        let config = Config {
+            paused: legacy.paused,
+            stake_asset: legacy.stake_asset,
+            oracle: legacy.oracle,
+            treasury_fee: legacy.treasury_fee,
+            keeper_reward: legacy.keeper_reward,
+            current_round_id: legacy.current_round_id,
+            genesis_start_once: legacy.genesis_start_once,
+            genesis_lock_once: legacy.genesis_lock_once,
+            betting_interval: legacy.betting_interval,
+            live_interval: legacy.live_interval,
+            buffer_seconds: legacy.buffer_seconds,
+            min_bet: Uint128::zero(),
+            max_bet: None,
+            max_user_stake: legacy.max_user_stake,
+            max_round_pool: None,
+            cancel_fee: legacy.cancel_fee,
+        };
+        CONFIG.save(deps.storage, &config)?;
+
+        Ok(())
+    }
+}
@@ -1,21 +1,119 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Decimal256, Int128, Order, Uint128};
+use cw20::Cw20ReceiveMsg;
+
+use crate::oracle::SignedPrice;
+use crate::state::{Bet, Config, OracleSource, StakeAsset};
 
 #[cw_serde]
 pub struct InstantiateMsg {
+    pub stake_asset: StakeAsset,
+    pub oracle: OracleSource,
     pub treasury_fee: u64,
+    /// Each role defaults to the instantiating owner
+    pub operator: Option<String>,
+    pub pauser: Option<String>,
+    pub treasury: Option<String>,
+    /// Basis points of the treasury fee paid to the keeper ending a round
+    pub keeper_reward: u64,
+    /// Smallest accepted bet or top-up
+    pub min_bet: Uint128,
+    /// Largest accepted bet or top-up, unlimited if not set or zero
+    pub max_bet: Option<Uint128>,
+    /// Most a user can stake in one round, unlimited if not set or zero
+    pub max_user_stake: Option<Uint128>,
+    /// Most that can be staked in one round across all users, unlimited if not set or zero
+    pub max_round_pool: Option<Uint128>,
+    /// Basis points of the stake kept by the treasury when a bet is cancelled
+    pub cancel_fee: u64,
+    pub betting_interval: u64,
+    pub live_interval: u64,
+    pub buffer_seconds: u64,
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
+    /// Places a bet, or tops up the caller's bet in the same direction
     PlaceBet { round_id: u64, direction: Direction},
+    Receive(Cw20ReceiveMsg),
+    /// Settles the caller's bets of the given rounds and pays out their winnings ledger,
+    /// including rewards credited by earlier settlements
     ClaimReward { round_id: u64},
-    StartRound { price: Uint128 },
-    LockRound {  price: Uint128 },
-    EndRound {  price: Uint128 },
+    ClaimRewards { round_ids: Vec<u64>, strict: bool },
+    ClaimRefund { round_id: u64 },
+    /// Withdraws the caller's bet from an open round, minus the cancellation fee
+    CancelBet { round_id: u64 },
+    /// Settles the next `limit` bets of a settled round, anyone can call it in chunks
+    ProcessSettlement { round_id: u64, limit: u32 },
+    WithdrawWinnings {},
+    CancelRound { round_id: u64 },
+    /// Also restarts a pipeline that missed a lock or end window, voiding its running rounds
+    GenesisStartRound {},
+    /// `price` is required with a manual oracle and `signed_price` with a signed oracle,
+    /// both are rejected otherwise
+    GenesisLockRound {
+        price: Option<Uint128>,
+        signed_price: Option<SignedPrice>,
+    },
+    ExecuteRound {
+        price: Option<Uint128>,
+        signed_price: Option<SignedPrice>,
+    },
     Pause {},
     Resume {},
-    Withdraw { amount: Uint128, recipient: String},
+    ClaimTreasury {},
+    UpdateConfig(UpdateConfigMsg),
+    /// Reassigns the operator, pauser and treasury roles, only the ones that are set
+    UpdateRoles {
+        operator: Option<String>,
+        pauser: Option<String>,
+        treasury: Option<String>,
+    },
+    /// Offers ownership to `new_owner`, who has `expires_in` seconds to accept it
+    TransferOwnership { new_owner: String, expires_in: u64 },
+    AcceptOwnership {},
+    CancelOwnershipTransfer {},
+    /// Starts a season over the rounds started between the two times, in seconds.
+    /// `payout_curve` is the prize of each rank from the first in basis points of the pool.
+    CreateSeason {
+        start_time: u64,
+        end_time: u64,
+        fee_share: u64,
+        payout_curve: Vec<u64>,
+    },
+    /// Adds the attached stake asset to a season's prize pool
+    FundSeason { season_id: u64 },
+    /// Pays the caller's prize once the season is over and all of its bets are settled
+    ClaimSeasonPrize { season_id: u64 },
+    /// Moves the part of a closed season's prize pool no rank can claim to the treasury
+    SweepSeason { season_id: u64 },
+}
+
+/// Owner config update, only the fields that are set are changed
+#[cw_serde]
+#[derive(Default)]
+pub struct UpdateConfigMsg {
+    pub betting_interval: Option<u64>,
+    pub live_interval: Option<u64>,
+    pub buffer_seconds: Option<u64>,
+    pub oracle: Option<OracleSource>,
+    pub keeper_reward: Option<u64>,
+    pub min_bet: Option<Uint128>,
+    /// Zero removes the limit, like for the caps below
+    pub max_bet: Option<Uint128>,
+    pub max_user_stake: Option<Uint128>,
+    pub max_round_pool: Option<Uint128>,
+    pub cancel_fee: Option<u64>,
+}
+
+#[cw_serde]
+pub struct MigrateMsg {}
+
+/// Hook messages accepted with `Cw20ExecuteMsg::Send`
+#[cw_serde]
+pub enum ReceiveMsg {
+    PlaceBet { round_id: u64, direction: Direction },
+    FundSeason { season_id: u64 },
 }
 
 #[cw_serde]
@@ -24,26 +122,115 @@ pub enum QueryMsg {
     #[returns(RoundResponse)]
     GetRoundDetails { round_id: u64 },
 
-    #[returns(Vec<RoundResponse>)]
-    GetAllRoundDetails {},
+    #[returns(RoundsResponse)]
+    GetAllRoundDetails {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
 
     #[returns(BetResponse)]
     GetUserPrediction { round_id: u64, user: String },
 
-    #[returns(Vec<BetResponse>)]
-    GetAllUserPredictions {},
+    /// Paged by `(round_id, user)`
+    #[returns(PredictionsResponse)]
+    GetAllUserPredictions {
+        start_after: Option<(u64, String)>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+
+    /// Only includes bets that have been settled by a claim or ProcessSettlement
+    #[returns(Uint128)]
+    GetWinnings { user: String },
+
+    /// Positions of `user`, latest round first and paged by round id
+    #[returns(PortfolioResponse)]
+    GetUserPortfolio {
+        user: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Payout per unit staked on each side if the round was settled now, net of fees
+    #[returns(OddsResponse)]
+    GetOdds { round_id: u64 },
+
+    /// Payout of a new bet on an open round if it won and the round was settled now
+    #[returns(SimulateBetResponse)]
+    SimulateBet {
+        round_id: u64,
+        direction: Direction,
+        amount: Uint128,
+    },
+
+    #[returns(Config)]
+    GetConfig {},
+
+    #[returns(TreasuryResponse)]
+    GetTreasury {},
 
     #[returns(bool)]
     IsPaused {},
 
-    #[returns(Vec<LeaderboardEntry>)]
-    GetLeaderboard {},
+    #[returns(RolesResponse)]
+    GetRoles {},
+
+    /// Bettors of a round in address order
+    #[returns(ParticipantsResponse)]
+    GetRoundParticipants {
+        round_id: u64,
+        start_after: Option<String>,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+
+    /// Best ranked users first, by net profit unless `sort_by` says otherwise
+    #[returns(LeaderboardResponse)]
+    GetLeaderboard {
+        sort_by: Option<LeaderboardSort>,
+        limit: Option<u32>,
+        start_after: Option<String>,
+    },
+
+    #[returns(SeasonResponse)]
+    GetSeason { season_id: u64 },
+
+    /// Best net profit of the season first
+    #[returns(LeaderboardResponse)]
+    GetSeasonLeaderboard {
+        season_id: u64,
+        limit: Option<u32>,
+        start_after: Option<String>,
+    },
+
+    #[returns(RankResponse)]
+    GetUserRank {
+        user: String,
+        sort_by: Option<LeaderboardSort>,
+    },
 
     #[returns(Uint128)]
     GetPool { round_id: u64 }
     
 }
 
+/// Order of list queries, ascending by default
+#[cw_serde]
+pub enum OrderBy {
+    Ascending,
+    Descending,
+}
+
+impl From<OrderBy> for Order {
+    fn from(order: OrderBy) -> Self {
+        match order {
+            OrderBy::Ascending => Order::Ascending,
+            OrderBy::Descending => Order::Descending,
+        }
+    }
+}
+
 #[cw_serde]
 // #[derive(Debug)]
 pub enum Direction {
@@ -51,6 +238,18 @@ pub enum Direction {
     Down
 }
 
+/// Lifecycle of a round. Transitions are enforced in `execute.rs`:
+/// Pending -> Open -> Locked -> Settled | Refunding, and Open | Locked -> Cancelled
+#[cw_serde]
+pub enum RoundStatus {
+    Pending,
+    Open,
+    Locked,
+    Settled,
+    Cancelled,
+    Refunding,
+}
+
 #[cw_serde]
 pub struct RoundResponse {
     pub id: u64,
@@ -64,8 +263,125 @@ pub struct RoundResponse {
     pub lock_price: Uint128,
     pub end_price: Uint128,
     pub result: Option<Direction>,
-    pub executed: bool,
-    pub participants: Vec<Addr>
+    pub reward_base: Uint128,
+    pub reward_amount: Uint128,
+    pub status: RoundStatus,
+    pub participant_count: u64,
+}
+
+/// List responses carry the key to pass as `start_after` for the next page, `None` on the
+/// last page
+#[cw_serde]
+pub struct RoundsResponse {
+    pub rounds: Vec<RoundResponse>,
+    pub next_key: Option<u64>,
+}
+
+#[cw_serde]
+pub struct PredictionsResponse {
+    pub predictions: Vec<Bet>,
+    pub next_key: Option<(u64, String)>,
+}
+
+#[cw_serde]
+pub struct ParticipantsResponse {
+    pub participants: Vec<Addr>,
+    pub next_key: Option<String>,
+}
+
+/// Metric the leaderboard is ranked by, ties are ordered by address
+#[cw_serde]
+pub enum LeaderboardSort {
+    NetProfit,
+    AmountWon,
+    WinCount,
+    Volume,
+}
+
+#[cw_serde]
+pub struct LeaderboardResponse {
+    pub entries: Vec<LeaderboardEntry>,
+    pub next_key: Option<String>,
+}
+
+#[cw_serde]
+pub struct SeasonResponse {
+    pub id: u64,
+    pub start_time: u64,
+    pub end_time: u64,
+    pub fee_share: u64,
+    pub payout_curve: Vec<u64>,
+    pub prize_pool: Uint128,
+    /// Bets of the season's rounds still waiting for settlement
+    pub open_bets: u64,
+    /// Prizes can be claimed once the season is closed
+    pub closed: bool,
+}
+
+/// Multipliers include the returned stake, a side nobody bet on has none
+#[cw_serde]
+pub struct OddsResponse {
+    pub round_id: u64,
+    pub bull_multiplier: Option<Decimal256>,
+    pub bear_multiplier: Option<Decimal256>,
+}
+
+#[cw_serde]
+pub struct SimulateBetResponse {
+    /// Stake plus winnings
+    pub payout: Uint128,
+}
+
+#[cw_serde]
+pub struct RankResponse {
+    /// 1 for the best ranked user, `None` for users who never placed a bet or are ranked
+    /// below the first 1000
+    pub rank: Option<u64>,
+    pub entry: Option<LeaderboardEntry>,
+}
+
+#[cw_serde]
+pub struct PositionResponse {
+    pub round_id: u64,
+    pub amount: Uint128,
+    pub direction: Direction,
+    pub status: RoundStatus,
+    pub result: Option<Direction>,
+    /// Reward of a winning bet or stake of a voided round, zero for a lost bet and `None`
+    /// until the round has ended
+    pub payout: Option<Uint128>,
+    pub claimed: bool,
+}
+
+/// Totals cover the returned page only, apart from `winnings`
+#[cw_serde]
+pub struct PortfolioResponse {
+    pub positions: Vec<PositionResponse>,
+    /// Stake in rounds that have not ended yet
+    pub pending_stake: Uint128,
+    /// Rewards not credited to the winnings ledger yet and refunds not claimed yet
+    pub claimable: Uint128,
+    /// Winnings ledger of the user across all rounds, paid by a claim or WithdrawWinnings
+    pub winnings: Uint128,
+    pub next_key: Option<u64>,
+}
+
+#[cw_serde]
+pub struct TreasuryResponse {
+    pub treasury: Addr,
+    pub accrued: Uint128,
+    pub withdrawn: Uint128,
+    pub available: Uint128,
+}
+
+#[cw_serde]
+pub struct RolesResponse {
+    pub owner: Addr,
+    pub operator: Addr,
+    pub pauser: Addr,
+    pub treasury: Addr,
+    pub pending_owner: Option<Addr>,
+    pub pending_owner_expires_at: Option<u64>,
 }
 
 #[cw_serde]
@@ -84,5 +400,6 @@ pub struct LeaderboardEntry {
     pub total_down: u64,
     pub amount_won: Uint128,
     pub amount_lost: Uint128,
-
+    /// Rewards minus the stakes of every settled bet
+    pub net_profit: Int128,
 }
\ No newline at end of file
@@ -0,0 +1,183 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{to_json_vec, Binary, DepsMut, Env, StdResult, Timestamp, Uint128};
+use sha2::{Digest, Sha256};
+
+use crate::error::ContractError;
+use crate::state::{OracleSource, SignatureScheme, LAST_PRICE_NONCE};
+
+/// Query interface a price-feed contract has to implement
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum OracleQueryMsg {
+    #[returns(PriceResponse)]
+    Price {},
+}
+
+#[cw_serde]
+pub struct PriceResponse {
+    pub price: Uint128,
+    /// Absolute confidence interval around `price`
+    pub confidence: Uint128,
+    /// Unix time in seconds the price was published at
+    pub publish_time: u64,
+}
+
+/// Price signed off-chain by the whitelisted signers of a `Signed` oracle
+#[cw_serde]
+pub struct SignedPrice {
+    pub price: Uint128,
+    /// Unix time in seconds the price was observed at, not before the lock or end time of
+    /// the rounds it is submitted for
+    pub timestamp: u64,
+    /// Must be greater than the nonce of the last accepted price
+    pub nonce: u64,
+    pub signatures: Vec<PriceSignature>,
+}
+
+#[cw_serde]
+pub struct PriceSignature {
+    /// Public key of the signer, it must be whitelisted in the oracle config
+    pub signer: Binary,
+    pub signature: Binary,
+}
+
+/// Document the signers sign. It is bound to the chain and contract so a signature
+/// cannot be replayed on another market.
+#[cw_serde]
+pub struct PricePayload {
+    pub chain_id: String,
+    pub contract: String,
+    pub price: Uint128,
+    pub timestamp: u64,
+    pub nonce: u64,
+}
+
+/// Bytes signed for a price: the JSON encoded `PricePayload`. Secp256k1 signers sign
+/// its sha256 digest, ed25519 signers sign the bytes directly.
+pub fn signed_price_message(
+    env: &Env,
+    price: Uint128,
+    timestamp: u64,
+    nonce: u64,
+) -> StdResult<Vec<u8>> {
+    to_json_vec(&PricePayload {
+        chain_id: env.block.chain_id.clone(),
+        contract: env.contract.address.to_string(),
+        price,
+        timestamp,
+        nonce,
+    })
+}
+
+/// Resolves the price used to lock and end rounds. `price` is the plain price sent with the
+/// message, which is only accepted when the oracle is in `Manual` mode, and `signed_price`
+/// is only accepted in `Signed` mode. A feed or signed price must be observed at or after
+/// `not_before`, the time the rounds it locks and ends are due, so a keeper or relayer
+/// cannot pick an older one.
+pub fn resolve_price(
+    deps: DepsMut,
+    env: &Env,
+    oracle: &OracleSource,
+    price: Option<Uint128>,
+    signed_price: Option<SignedPrice>,
+    not_before: Timestamp,
+) -> Result<Uint128, ContractError> {
+    if signed_price.is_some() && !matches!(oracle, OracleSource::Signed { .. }) {
+        return Err(ContractError::SignedPriceNotAllowed {});
+    }
+
+    match oracle {
+        OracleSource::Manual => price.ok_or(ContractError::MissingPrice {}),
+        OracleSource::Feed {
+            contract_addr,
+            max_age,
+            max_confidence_bps,
+        } => {
+            if price.is_some() {
+                return Err(ContractError::ManualPriceNotAllowed {});
+            }
+
+            let feed: PriceResponse = deps
+                .querier
+                .query_wasm_smart(contract_addr, &OracleQueryMsg::Price {})?;
+
+            // Reject prices older than the allowed age
+            let age = env.block.time.seconds().saturating_sub(feed.publish_time);
+            if age > *max_age {
+                return Err(ContractError::StalePrice { age });
+            }
+            if feed.publish_time < not_before.seconds() {
+                return Err(ContractError::PriceBeforeRound {});
+            }
+
+            // Reject prices whose confidence interval is too wide relative to the price,
+            // compared in 256 bits so no feed value can overflow
+            if feed.confidence.full_mul(10_000u64) > feed.price.full_mul(*max_confidence_bps) {
+                return Err(ContractError::PriceConfidenceTooLow {});
+            }
+
+            Ok(feed.price)
+        }
+        OracleSource::Signed {
+            signers,
+            threshold,
+            scheme,
+            max_age,
+        } => {
+            if price.is_some() {
+                return Err(ContractError::ManualPriceNotAllowed {});
+            }
+            let signed = signed_price.ok_or(ContractError::MissingSignedPrice {})?;
+
+            // Replay protection: nonces must be strictly increasing and prices recent
+            let last_nonce = LAST_PRICE_NONCE.may_load(deps.storage)?;
+            if last_nonce.is_some_and(|last| signed.nonce <= last) {
+                return Err(ContractError::PriceNonceUsed {});
+            }
+            let now = env.block.time.seconds();
+            if signed.timestamp > now {
+                return Err(ContractError::InvalidPriceTimestamp {});
+            }
+            let age = now - signed.timestamp;
+            if age > *max_age {
+                return Err(ContractError::StalePrice { age });
+            }
+            if signed.timestamp < not_before.seconds() {
+                return Err(ContractError::PriceBeforeRound {});
+            }
+
+            let message = signed_price_message(env, signed.price, signed.timestamp, signed.nonce)?;
+            let hash = Sha256::digest(&message);
+
+            // Count each whitelisted signer once, malformed signatures are not counted
+            let mut valid: Vec<&Binary> = vec![];
+            for sig in signed.signatures.iter() {
+                if !signers.contains(&sig.signer) || valid.contains(&&sig.signer) {
+                    continue;
+                }
+                let verified = match scheme {
+                    SignatureScheme::Secp256k1 => {
+                        deps.api
+                            .secp256k1_verify(&hash, &sig.signature, &sig.signer)
+                    }
+                    SignatureScheme::Ed25519 => {
+                        deps.api
+                            .ed25519_verify(&message, &sig.signature, &sig.signer)
+                    }
+                };
+                if verified.unwrap_or(false) {
+                    valid.push(&sig.signer);
+                }
+            }
+            if valid.len() < *threshold as usize {
+                return Err(ContractError::NotEnoughSignatures {
+                    valid: valid.len() as u32,
+                    required: *threshold,
+                });
+            }
+
+            LAST_PRICE_NONCE.save(deps.storage, &signed.nonce)?;
+            Ok(signed.price)
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Payout and fee math shared by settlement, claims and the odds queries.
+//!
+//! Everything is computed in 256 bits and checked. Every amount is rounded down, so the
+//! treasury fee and the winner shares of a round never add up to more than its total pool.
+//! The rounding remainder of the winner shares is dust, paid to the treasury once every
+//! bet of the round is settled.
+
+use cosmwasm_std::{Decimal256, Int128, StdResult, Uint128, Uint256};
+
+use crate::msg::Direction;
+use crate::state::{Bet, Round};
+
+const BPS_DENOMINATOR: u64 = 10_000;
+
+/// `value * numerator / denominator`, rounded down
+fn mul_div_floor(value: Uint128, numerator: Uint128, denominator: Uint128) -> StdResult<Uint128> {
+    let result = Uint256::from(value)
+        .checked_mul(numerator.into())?
+        .checked_div(denominator.into())?;
+    Ok(result.try_into()?)
+}
+
+/// `bps` basis points of `amount`, rounded down
+pub fn bps_share(amount: Uint128, bps: u64) -> StdResult<Uint128> {
+    mul_div_floor(amount, bps.into(), BPS_DENOMINATOR.into())
+}
+
+/// Treasury fee taken from a round's total pool at settlement, rounded down
+pub fn treasury_fee(total_pool: Uint128, fee_bps: u64) -> StdResult<Uint128> {
+    bps_share(total_pool, fee_bps)
+}
+
+/// Part of the total pool shared by the winners once the treasury fee is taken
+pub fn reward_amount(total_pool: Uint128, fee_bps: u64) -> StdResult<Uint128> {
+    Ok(total_pool.checked_sub(treasury_fee(total_pool, fee_bps)?)?)
+}
+
+/// Stake on one side of a round
+pub fn side_pool(round: &Round, direction: &Direction) -> Uint128 {
+    match direction {
+        Direction::Up => round.bull_pool,
+        Direction::Down => round.bear_pool,
+    }
+}
+
+/// Share of `reward_amount` owed to `amount` staked on the winning side of `reward_base`,
+/// rounded down
+pub fn winner_share(
+    amount: Uint128,
+    reward_amount: Uint128,
+    reward_base: Uint128,
+) -> StdResult<Uint128> {
+    mul_div_floor(amount, reward_amount, reward_base)
+}
+
+/// Payout per unit staked on a winning side of `reward_base`, `None` while nobody has bet
+/// on that side. A ratio of two 128-bit amounts always fits in a `Decimal256`.
+pub fn multiplier(reward_amount: Uint128, reward_base: Uint128) -> Option<Decimal256> {
+    Decimal256::checked_from_ratio(reward_amount, reward_base).ok()
+}
+
+/// Share of the round's reward amount owed to `bet`, `None` if the bet did not win
+pub fn bet_reward(round: &Round, bet: &Bet) -> StdResult<Option<Uint128>> {
+    if round.result.as_ref() != Some(&bet.direction) {
+        return Ok(None);
+    }
+
+    // winners are in the reward base, so it is never empty here
+    winner_share(bet.amount, round.reward_amount, round.reward_base).map(Some)
+}
+
+/// Reward of a settled bet minus its stake, the whole stake is lost by a losing bet
+pub fn bet_net_profit(round: &Round, bet: &Bet) -> StdResult<Int128> {
+    let reward = bet_reward(round, bet)?.unwrap_or_default();
+    Ok(Int128::try_from(reward)?.checked_sub(Int128::try_from(bet.amount)?)?)
+}
+
+/// Payout of a new bet of `amount` on `direction` if it won and the round was settled now
+/// with a treasury fee of `fee_bps`
+pub fn simulate_bet(
+    round: &Round,
+    direction: &Direction,
+    amount: Uint128,
+    fee_bps: u64,
+) -> StdResult<Uint128> {
+    let reward_amount = reward_amount(round.total_pool.checked_add(amount)?, fee_bps)?;
+    winner_share(
+        amount,
+        reward_amount,
+        side_pool(round, direction).checked_add(amount)?,
+    )
+}
+
+/// What is left of a round's reward amount once `paid` has been paid to its winners
+pub fn dust(reward_amount: Uint128, paid: Uint128) -> StdResult<Uint128> {
+    Ok(reward_amount.checked_sub(paid)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn fees_round_down() {
+        assert_eq!(
+            treasury_fee(Uint128::new(149), 200).unwrap(),
+            Uint128::new(2)
+        );
+        assert_eq!(
+            reward_amount(Uint128::new(149), 200).unwrap(),
+            Uint128::new(147)
+        );
+        assert_eq!(
+            treasury_fee(Uint128::new(49), 200).unwrap(),
+            Uint128::zero()
+        );
+        assert_eq!(bps_share(Uint128::new(7), 10_000).unwrap(), Uint128::new(7));
+    }
+
+    #[test]
+    fn winner_shares_round_down_and_leave_dust() {
+        let reward_amount = Uint128::new(100);
+        let reward_base = Uint128::new(3);
+        let share = winner_share(Uint128::one(), reward_amount, reward_base).unwrap();
+        assert_eq!(share, Uint128::new(33));
+        assert_eq!(
+            dust(reward_amount, share * Uint128::new(3)).unwrap(),
+            Uint128::one()
+        );
+    }
+
+    #[test]
+    fn large_pools_do_not_overflow() {
+        let pool = Uint128::MAX;
+        assert_eq!(
+            treasury_fee(pool, 10_000).unwrap(),
+            Uint128::MAX,
+            "a full fee takes the whole pool"
+        );
+        let reward_amount = reward_amount(pool, 200).unwrap();
+        assert_eq!(
+            winner_share(pool, reward_amount, pool).unwrap(),
+            reward_amount
+        );
+    }
+
+    #[test]
+    fn invalid_inputs_are_errors() {
+        assert!(reward_amount(Uint128::new(100), 20_000).is_err());
+        assert!(winner_share(Uint128::one(), Uint128::one(), Uint128::zero()).is_err());
+        assert!(dust(Uint128::one(), Uint128::new(2)).is_err());
+        assert_eq!(multiplier(Uint128::one(), Uint128::zero()), None);
+        assert_eq!(
+            multiplier(Uint128::MAX, Uint128::one()),
+            Some(Decimal256::from_ratio(Uint128::MAX, 1u128))
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn payouts_and_fees_never_exceed_the_pool(
+            stakes in prop::collection::vec((1u128..=u128::MAX >> 8, any::<bool>()), 1..40),
+            fee_bps in 0u64..=10_000,
+        ) {
+            let stakes: Vec<(Uint128, bool)> = stakes
+                .into_iter()
+                .map(|(amount, up)| (Uint128::new(amount), up))
+                .collect();
+            let total_pool: Uint128 = stakes.iter().map(|(amount, _)| *amount).sum();
+            let reward_base: Uint128 = stakes
+                .iter()
+                .filter(|(_, up)| *up)
+                .map(|(amount, _)| *amount)
+                .sum();
+            prop_assume!(!reward_base.is_zero());
+
+            let fee = treasury_fee(total_pool, fee_bps).unwrap();
+            let reward_amount = reward_amount(total_pool, fee_bps).unwrap();
+            let mut paid = Uint256::zero();
+            for (amount, _) in stakes.iter().filter(|(_, up)| *up) {
+                paid += Uint256::from(winner_share(*amount, reward_amount, reward_base).unwrap());
+            }
+            let paid = Uint128::try_from(paid).unwrap();
+            let dust = dust(reward_amount, paid).unwrap();
+
+            prop_assert!(Uint256::from(paid) + Uint256::from(fee) <= Uint256::from(total_pool));
+            prop_assert_eq!(paid + fee + dust, total_pool);
+            // every winner loses less than one unit to rounding
+            prop_assert!(dust.u128() < stakes.len() as u128);
+        }
+
+        #[test]
+        fn fee_splits_never_exceed_the_fee(
+            total_pool in any::<u128>(),
+            fee_bps in 0u64..=10_000,
+            keeper_bps in 0u64..=10_000,
+            season_bps in 0u64..=10_000,
+        ) {
+            let total_pool = Uint128::new(total_pool);
+            let fee = treasury_fee(total_pool, fee_bps).unwrap();
+            let keeper_reward = bps_share(fee, keeper_bps).unwrap();
+            let season_share = bps_share(fee - keeper_reward, season_bps).unwrap();
+
+            prop_assert!(fee <= total_pool);
+            prop_assert!(keeper_reward + season_share <= fee);
+            prop_assert_eq!(reward_amount(total_pool, fee_bps).unwrap() + fee, total_pool);
+        }
+
+        #[test]
+        fn simulated_payout_matches_the_settled_share(
+            bull_pool in 1u128..=u128::MAX >> 4,
+            bear_pool in 1u128..=u128::MAX >> 4,
+            amount in 1u128..=u128::MAX >> 4,
+            fee_bps in 0u64..=10_000,
+        ) {
+            let round = Round {
+                id: 1,
+                bull_pool: Uint128::new(bull_pool),
+                bear_pool: Uint128::new(bear_pool),
+                total_pool: Uint128::new(bull_pool + bear_pool),
+                start_time: Default::default(),
+                lock_time: Default::default(),
+                end_time: Default::default(),
+                start_price: Uint128::zero(),
+                lock_price: Uint128::zero(),
+                end_price: Uint128::zero(),
+                result: None,
+                reward_base: Uint128::zero(),
+                reward_amount: Uint128::zero(),
+                status: crate::msg::RoundStatus::Open,
+                participant_count: 2,
+            };
+            let amount = Uint128::new(amount);
+            let payout = simulate_bet(&round, &Direction::Up, amount, fee_bps).unwrap();
+
+            // Place the bet and settle the round the way end_round does
+            let mut settled = round.clone();
+            settled.bull_pool += amount;
+            settled.total_pool += amount;
+            settled.result = Some(Direction::Up);
+            settled.reward_base = side_pool(&settled, &Direction::Up);
+            settled.reward_amount = reward_amount(settled.total_pool, fee_bps).unwrap();
+            let bet = Bet {
+                amount,
+                direction: Direction::Up,
+                claimed: false,
+                settled: false,
+            };
+            prop_assert_eq!(bet_reward(&settled, &bet).unwrap(), Some(payout));
+            prop_assert!(payout <= settled.reward_amount);
+        }
+    }
+}
@@ -1,60 +1,158 @@
-use cosmwasm_std::{to_json_binary, Addr, Binary, Deps, Env, Order, StdResult, Uint128};
+use cosmwasm_std::{to_json_binary, Addr, Binary, Deps, Env, Order, StdError, StdResult, Uint128};
 
-use crate::state::{ Bet, Config, Round, CONFIG, LEADERBOARDENTRY, ROUNDS, USER_BETS};
-use crate::msg::{LeaderboardEntry, QueryMsg};
+use crate::state::{ Bet, Config, Roles, Round, Season, TreasuryBalance, CONFIG, LEADERBOARDENTRY, PENDING_OWNER, ROLES, ROUNDS, SEASONS, SEASON_ENTRIES, TREASURY_BALANCE, USER_BETS, WINNINGS};
+use crate::payout::{self, bet_reward};
+use crate::msg::{Direction, LeaderboardEntry, LeaderboardResponse, LeaderboardSort, OddsResponse, RankResponse, SeasonResponse, SimulateBetResponse, OrderBy, ParticipantsResponse, PortfolioResponse, PositionResponse, PredictionsResponse, QueryMsg, RolesResponse, RoundResponse, RoundStatus, RoundsResponse, TreasuryResponse};
+use cw_storage_plus::{Bound, KeyDeserialize, MultiIndex, Prefixer, PrimaryKey};
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+/// Ranks are only counted this far, each user ranked above costs a read
+const MAX_RANK: usize = 1_000;
+
+/// Clamps the requested page size to `MAX_LIMIT`
+fn page_limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
+/// Range bounds starting after `start`, in either direction
+fn page_bounds<B>(start: Option<B>, order: Order) -> (Option<B>, Option<B>) {
+    match order {
+        Order::Ascending => (start, None),
+        Order::Descending => (None, start),
+    }
+}
+
+/// Collects up to `limit` items and returns the key of the last one when more are left
+fn paginate<K: Clone, T>(
+    items: impl Iterator<Item = StdResult<(K, T)>>,
+    limit: usize,
+) -> StdResult<(Vec<T>, Option<K>)> {
+    let mut items: Vec<(K, T)> = items.take(limit + 1).collect::<StdResult<_>>()?;
+    let next_key = if items.len() > limit {
+        items.truncate(limit);
+        items.last().map(|(key, _)| key.clone())
+    } else {
+        None
+    };
+
+    Ok((items.into_iter().map(|(_, item)| item).collect(), next_key))
+}
 
 
 
 
 pub fn query (
     deps: Deps,
-    _env: Env,
+    env: Env,
     msg: QueryMsg
 ) -> StdResult<Binary> {
     match  msg {
         QueryMsg::GetRoundDetails { round_id } => {
             to_json_binary(&get_round_details(deps, round_id)?)
         },
-        QueryMsg::GetAllRoundDetails {  } => {
-            to_json_binary(&get_all_round_details(deps)?)
+        QueryMsg::GetAllRoundDetails { start_after, limit, order } => {
+            to_json_binary(&get_all_round_details(deps, start_after, limit, order)?)
         },
         QueryMsg::GetUserPrediction { round_id, user } => {
             let addr = deps.api.addr_validate(&user)?;
             to_json_binary(&get_user_prediction(deps, round_id, addr)?)
         },
-        QueryMsg::GetAllUserPredictions {  } => {
-            to_json_binary(&get_all_user_prediction(deps)?)
+        QueryMsg::GetAllUserPredictions { start_after, limit, order } => {
+            to_json_binary(&get_all_user_prediction(deps, start_after, limit, order)?)
+        },
+        QueryMsg::GetWinnings { user } => {
+            let addr = deps.api.addr_validate(&user)?;
+            to_json_binary(&get_winnings(deps, addr)?)
+        },
+        QueryMsg::GetUserPortfolio { user, start_after, limit } => {
+            let addr = deps.api.addr_validate(&user)?;
+            to_json_binary(&get_user_portfolio(deps, addr, start_after, limit)?)
         },
         QueryMsg::GetPool { round_id } => {
             to_json_binary(&get_pool(deps, round_id)?)
         },
+        QueryMsg::GetOdds { round_id } => to_json_binary(&get_odds(deps, round_id)?),
+        QueryMsg::SimulateBet { round_id, direction, amount } => {
+            to_json_binary(&simulate_bet(deps, round_id, direction, amount)?)
+        }
+        QueryMsg::GetConfig {  } => {
+            let config: Config = CONFIG.load(deps.storage)?;
+            to_json_binary(&config)
+        },
+        QueryMsg::GetTreasury {  } => {
+            to_json_binary(&get_treasury(deps)?)
+        },
         QueryMsg::IsPaused {  } => {
             let config: Config = CONFIG.load(deps.storage)?;
             to_json_binary(&config.paused)
         },
-        QueryMsg::GetLeaderboard {  } => {
-            to_json_binary(&get_leaderboard(deps)?)
+        QueryMsg::GetRoles {  } => {
+            to_json_binary(&get_roles(deps)?)
+        },
+        QueryMsg::GetRoundParticipants { round_id, start_after, limit, order } => {
+            to_json_binary(&get_round_participants(deps, round_id, start_after, limit, order)?)
+        },
+        QueryMsg::GetLeaderboard { sort_by, limit, start_after } => {
+            to_json_binary(&get_leaderboard(deps, sort_by, limit, start_after)?)
+        }
+        QueryMsg::GetSeason { season_id } => to_json_binary(&get_season(deps, env, season_id)?),
+        QueryMsg::GetSeasonLeaderboard { season_id, limit, start_after } => {
+            to_json_binary(&get_season_leaderboard(deps, season_id, limit, start_after)?)
+        }
+        QueryMsg::GetUserRank { user, sort_by } => {
+            let addr = deps.api.addr_validate(&user)?;
+            to_json_binary(&get_user_rank(deps, addr, sort_by)?)
         }
      } 
 }
 
 
-fn get_round_details(deps: Deps, round_id: u64) -> StdResult<Round> {
+fn get_round_details(deps: Deps, round_id: u64) -> StdResult<RoundResponse> {
     let round: Round = ROUNDS.load(deps.storage, round_id)?;
 
-    Ok(round)
+    Ok(round.into())
 }
 
-fn get_all_round_details(deps: Deps) -> StdResult<Vec<Round>> {
-    let rounds: StdResult<Vec<_>> = ROUNDS
-    .range(deps.storage, None, None, Order::Ascending)
-    .map(|item| {
-        let (_, round) = item?;
-        Ok(round)
-    })
-    .collect();
+fn get_all_round_details(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<RoundsResponse> {
+    let order: Order = order.unwrap_or(OrderBy::Ascending).into();
+    let (min, max) = page_bounds(start_after.map(Bound::exclusive), order);
 
-    rounds
+    let (rounds, next_key) = paginate(
+        ROUNDS
+            .range(deps.storage, min, max, order)
+            .map(|item| item.map(|(id, round)| (id, RoundResponse::from(round)))),
+        page_limit(limit),
+    )?;
+
+    Ok(RoundsResponse { rounds, next_key })
+}
+
+fn get_round_participants(
+    deps: Deps,
+    round_id: u64,
+    start_after: Option<String>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<ParticipantsResponse> {
+    let order: Order = order.unwrap_or(OrderBy::Ascending).into();
+    let start_after = start_after.map(|addr| deps.api.addr_validate(&addr)).transpose()?;
+    let (min, max) = page_bounds(start_after.as_ref().map(Bound::exclusive), order);
+
+    let (participants, next_key) = paginate(
+        USER_BETS
+            .prefix(round_id)
+            .keys(deps.storage, min, max, order)
+            .map(|item| item.map(|user| (user.to_string(), user))),
+        page_limit(limit),
+    )?;
+
+    Ok(ParticipantsResponse { participants, next_key })
 }
 
 fn get_user_prediction(deps: Deps, round_id: u64, user: Addr) -> StdResult<Bet> {
@@ -63,15 +161,143 @@ fn get_user_prediction(deps: Deps, round_id: u64, user: Addr) -> StdResult<Bet>
     Ok(bet)
 }
 
-fn get_all_user_prediction(deps: Deps) -> StdResult<Vec<Bet>> {
-    let bets: StdResult<Vec<_>> = USER_BETS
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|item| {
-            let (_, bet) = item?;
-            Ok(bet)
-        })
-        .collect();
-    bets
+fn get_all_user_prediction(
+    deps: Deps,
+    start_after: Option<(u64, String)>,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<PredictionsResponse> {
+    let order: Order = order.unwrap_or(OrderBy::Ascending).into();
+    let start_after = start_after
+        .map(|(round_id, user)| deps.api.addr_validate(&user).map(|user| (round_id, user)))
+        .transpose()?;
+    let start = start_after.as_ref().map(|(round_id, user)| Bound::exclusive((*round_id, user)));
+    let (min, max) = page_bounds(start, order);
+
+    let (predictions, next_key) = paginate(
+        USER_BETS
+            .range(deps.storage, min, max, order)
+            .map(|item| item.map(|((round_id, user), bet)| ((round_id, user.to_string()), bet))),
+        page_limit(limit),
+    )?;
+
+    Ok(PredictionsResponse { predictions, next_key })
+}
+
+fn get_winnings(deps: Deps, user: Addr) -> StdResult<Uint128> {
+    let balance: Uint128 = WINNINGS.may_load(deps.storage, &user)?.unwrap_or_default();
+
+    Ok(balance)
+}
+
+fn get_user_portfolio(
+    deps: Deps,
+    user: Addr,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<PortfolioResponse> {
+    let max = start_after.map(|round_id| Bound::exclusive((round_id, user.clone())));
+    let (bets, next_key) = paginate(
+        USER_BETS
+            .idx
+            .user
+            .prefix(user.clone())
+            .range(deps.storage, None, max, Order::Descending)
+            .map(|item| item.map(|((round_id, _), bet)| (round_id, (round_id, bet)))),
+        page_limit(limit),
+    )?;
+
+    // Totals are over the page, rewards already credited are counted in the ledger
+    let mut positions: Vec<PositionResponse> = vec![];
+    let mut pending_stake = Uint128::zero();
+    let mut claimable = Uint128::zero();
+    for (round_id, bet) in bets {
+        let round: Round = ROUNDS.load(deps.storage, round_id)?;
+
+        let payout = if round.is_voided() {
+            if !bet.claimed {
+                claimable += bet.amount;
+            }
+            Some(bet.amount)
+        } else if round.status == RoundStatus::Settled {
+            let reward = bet_reward(&round, &bet)?.unwrap_or_default();
+            if !bet.settled {
+                claimable += reward;
+            }
+            Some(reward)
+        } else {
+            pending_stake += bet.amount;
+            None
+        };
+
+        positions.push(PositionResponse {
+            round_id,
+            amount: bet.amount,
+            direction: bet.direction,
+            status: round.status,
+            result: round.result,
+            payout,
+            claimed: bet.claimed,
+        });
+    }
+    let winnings: Uint128 = WINNINGS.may_load(deps.storage, &user)?.unwrap_or_default();
+
+    Ok(PortfolioResponse { positions, pending_stake, claimable, winnings, next_key })
+}
+
+fn get_odds(deps: Deps, round_id: u64) -> StdResult<OddsResponse> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let round: Round = ROUNDS.load(deps.storage, round_id)?;
+
+    // Settled rounds keep the reward amount of the fee they were settled with
+    let reward_amount = match round.status {
+        RoundStatus::Settled => round.reward_amount,
+        _ => payout::reward_amount(round.total_pool, config.treasury_fee)?,
+    };
+
+    Ok(OddsResponse {
+        round_id,
+        bull_multiplier: payout::multiplier(reward_amount, round.bull_pool),
+        bear_multiplier: payout::multiplier(reward_amount, round.bear_pool),
+    })
+}
+
+fn simulate_bet(deps: Deps, round_id: u64, direction: Direction, amount: Uint128) -> StdResult<SimulateBetResponse> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let round: Round = ROUNDS.load(deps.storage, round_id)?;
+    if round.status != RoundStatus::Open {
+        return Err(StdError::generic_err("Round is not open for bets"));
+    }
+
+    Ok(SimulateBetResponse {
+        payout: payout::simulate_bet(&round, &direction, amount, config.treasury_fee)?,
+    })
+}
+
+fn get_treasury(deps: Deps) -> StdResult<TreasuryResponse> {
+    let roles: Roles = ROLES.load(deps.storage)?;
+    let treasury: TreasuryBalance = TREASURY_BALANCE.load(deps.storage)?;
+
+    Ok(TreasuryResponse {
+        treasury: roles.treasury,
+        accrued: treasury.accrued,
+        withdrawn: treasury.withdrawn,
+        available: treasury.available(),
+    })
+}
+
+fn get_roles(deps: Deps) -> StdResult<RolesResponse> {
+    let roles: Roles = ROLES.load(deps.storage)?;
+    let pending = PENDING_OWNER.may_load(deps.storage)?;
+
+    Ok(RolesResponse {
+        owner: roles.owner,
+        operator: roles.operator,
+        pauser: roles.pauser,
+        treasury: roles.treasury,
+        pending_owner_expires_at: pending.as_ref().map(|p| p.expires_at.seconds()),
+        pending_owner: pending.map(|p| p.owner),
+    })
 }
 
 fn get_pool(deps:Deps, round_id: u64) -> StdResult<Uint128> {
@@ -80,13 +306,132 @@ fn get_pool(deps:Deps, round_id: u64) -> StdResult<Uint128> {
     Ok(round.total_pool)
 }
 
-fn get_leaderboard(deps: Deps) -> StdResult<Vec<LeaderboardEntry>> {
-    let boards: StdResult<Vec<_>> = LEADERBOARDENTRY
-        .range(deps.storage, None, None, Order::Ascending)
-        .map(|item| {
-            let (_, lb) = item?;
-            Ok(lb)
+fn get_leaderboard(
+    deps: Deps,
+    sort_by: Option<LeaderboardSort>,
+    limit: Option<u32>,
+    start_after: Option<String>,
+) -> StdResult<LeaderboardResponse> {
+    // Pages continue from the ranking key of the last user returned
+    let start_after: Option<LeaderboardEntry> = start_after
+        .map(|addr| {
+            let addr = deps.api.addr_validate(&addr)?;
+            LEADERBOARDENTRY.load(deps.storage, &addr)
+        })
+        .transpose()?;
+    let limit = page_limit(limit);
+
+    let idx = &LEADERBOARDENTRY.idx;
+    let (entries, next_key) = match sort_by.unwrap_or(LeaderboardSort::NetProfit) {
+        LeaderboardSort::NetProfit => {
+            ranked_page(deps, &idx.net_profit, |entry| entry.net_profit.i128(), start_after, limit)
+        }
+        LeaderboardSort::AmountWon => {
+            ranked_page(deps, &idx.amount_won, |entry| entry.amount_won.u128(), start_after, limit)
+        }
+        LeaderboardSort::WinCount => {
+            ranked_page(deps, &idx.win_count, |entry| entry.total_won, start_after, limit)
+        }
+        LeaderboardSort::Volume => {
+            ranked_page(deps, &idx.volume, |entry| entry.total_amount_played.u128(), start_after, limit)
+        }
+    }?;
+
+    Ok(LeaderboardResponse { entries, next_key })
+}
+
+/// Leaderboard entries in descending order of a ranking index
+fn ranked_page<'a, IK>(
+    deps: Deps,
+    index: &MultiIndex<'a, IK, LeaderboardEntry, Addr>,
+    key: impl Fn(&LeaderboardEntry) -> IK,
+    start_after: Option<LeaderboardEntry>,
+    limit: usize,
+) -> StdResult<(Vec<LeaderboardEntry>, Option<String>)>
+where
+    IK: PrimaryKey<'a> + Prefixer<'a> + KeyDeserialize,
+{
+    let max = start_after.map(|entry| Bound::exclusive((key(&entry), entry.user)));
+
+    paginate(
+        index
+            .range(deps.storage, None, max, Order::Descending)
+            .map(|item| item.map(|(user, entry)| (user.to_string(), entry))),
+        limit,
+    )
+}
+
+fn get_season(deps: Deps, env: Env, season_id: u64) -> StdResult<SeasonResponse> {
+    let season: Season = SEASONS.load(deps.storage, season_id)?;
+
+    Ok(SeasonResponse {
+        id: season.id,
+        start_time: season.start_time.seconds(),
+        end_time: season.end_time.seconds(),
+        closed: season.is_closed(env.block.time),
+        fee_share: season.fee_share,
+        payout_curve: season.payout_curve,
+        prize_pool: season.prize_pool,
+        open_bets: season.open_bets,
+    })
+}
+
+fn get_season_leaderboard(
+    deps: Deps,
+    season_id: u64,
+    limit: Option<u32>,
+    start_after: Option<String>,
+) -> StdResult<LeaderboardResponse> {
+    let max = start_after
+        .map(|addr| -> StdResult<_> {
+            let addr = deps.api.addr_validate(&addr)?;
+            let entry = SEASON_ENTRIES.load(deps.storage, (season_id, &addr))?;
+            Ok(Bound::exclusive((entry.net_profit.i128(), (season_id, addr))))
         })
-        .collect();
-    boards
-}
\ No newline at end of file
+        .transpose()?;
+
+    let (entries, next_key) = paginate(
+        SEASON_ENTRIES
+            .idx
+            .net_profit
+            .sub_prefix(season_id)
+            .range(deps.storage, None, max, Order::Descending)
+            .filter(|item| item.as_ref().map_or(true, |(_, entry)| entry.has_settled_bets()))
+            .map(|item| item.map(|((_, user), entry)| (user.to_string(), entry))),
+        page_limit(limit),
+    )?;
+
+    Ok(LeaderboardResponse { entries, next_key })
+}
+
+fn get_user_rank(deps: Deps, user: Addr, sort_by: Option<LeaderboardSort>) -> StdResult<RankResponse> {
+    let Some(entry) = LEADERBOARDENTRY.may_load(deps.storage, &user)? else {
+        return Ok(RankResponse { rank: None, entry: None });
+    };
+
+    let idx = &LEADERBOARDENTRY.idx;
+    let ahead = match sort_by.unwrap_or(LeaderboardSort::NetProfit) {
+        LeaderboardSort::NetProfit => users_ahead(deps, &idx.net_profit, entry.net_profit.i128(), &user),
+        LeaderboardSort::AmountWon => users_ahead(deps, &idx.amount_won, entry.amount_won.u128(), &user),
+        LeaderboardSort::WinCount => users_ahead(deps, &idx.win_count, entry.total_won, &user),
+        LeaderboardSort::Volume => users_ahead(deps, &idx.volume, entry.total_amount_played.u128(), &user),
+    };
+
+    let rank = (ahead < MAX_RANK).then_some(ahead as u64 + 1);
+
+    Ok(RankResponse { rank, entry: Some(entry) })
+}
+
+/// Number of users ranked above the index key `(value, user)`, counted up to `MAX_RANK`
+fn users_ahead<'a, IK>(
+    deps: Deps,
+    index: &MultiIndex<'a, IK, LeaderboardEntry, Addr>,
+    value: IK,
+    user: &Addr,
+) -> usize
+where
+    IK: PrimaryKey<'a> + Prefixer<'a> + KeyDeserialize,
+{
+    let min = Bound::exclusive((value, user.clone()));
+    index.keys_raw(deps.storage, Some(min), None, Order::Ascending).take(MAX_RANK).count()
+}
@@ -0,0 +1,342 @@
+use cosmwasm_std::{
+    Addr, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult, Storage, Timestamp,
+    Uint128,
+};
+use cw_storage_plus::Bound;
+
+use crate::error::ContractError;
+use crate::execute::native_stake;
+use crate::msg::LeaderboardEntry;
+use crate::payout;
+use crate::state::{
+    Bet, Config, Roles, Round, Season, CONFIG, ROLES, SEASONS, SEASON_ENTRIES, SEASON_PRIZES,
+    SEASON_SWEPT, TREASURY_BALANCE,
+};
+
+/// Season covering the rounds started at `time`, if any
+pub fn season_at(storage: &dyn Storage, time: Timestamp) -> StdResult<Option<Season>> {
+    let latest = SEASONS
+        .range(storage, None, None, Order::Descending)
+        .map(|item| item.map(|(_, season)| season))
+        .find(|season| {
+            season
+                .as_ref()
+                .map_or(true, |season| season.start_time <= time)
+        })
+        .transpose()?;
+
+    Ok(latest.filter(|season| time < season.end_time))
+}
+
+/// Counts a new bet in the season of its round
+pub fn record_bet(
+    storage: &mut dyn Storage,
+    round: &Round,
+    user: &Addr,
+    bet: &Bet,
+) -> StdResult<()> {
+    update_entry(storage, round, user, |season, entry| {
+        entry.record_bet(bet);
+        season.open_bets += 1;
+        Ok(())
+    })
+}
+
+/// Counts stake added to an existing bet in the season of its round
+pub fn record_top_up(
+    storage: &mut dyn Storage,
+    round: &Round,
+    user: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    update_entry(storage, round, user, |_, entry| {
+        entry.record_top_up(amount);
+        Ok(())
+    })
+}
+
+/// Applies the outcome of a settled bet to the season of its round
+pub fn record_outcome(
+    storage: &mut dyn Storage,
+    round: &Round,
+    user: &Addr,
+    bet: &Bet,
+) -> StdResult<()> {
+    update_entry(storage, round, user, |season, entry| {
+        entry.record_outcome(round, bet)?;
+        season.open_bets -= 1;
+        Ok(())
+    })
+}
+
+/// Updates the user's entry in the season of `round`, rounds outside of any season are
+/// not tracked
+fn update_entry(
+    storage: &mut dyn Storage,
+    round: &Round,
+    user: &Addr,
+    action: impl FnOnce(&mut Season, &mut LeaderboardEntry) -> StdResult<()>,
+) -> StdResult<()> {
+    let Some(mut season) = season_at(storage, round.start_time)? else {
+        return Ok(());
+    };
+
+    let mut entry = SEASON_ENTRIES
+        .may_load(storage, (season.id, user))?
+        .unwrap_or_else(|| LeaderboardEntry::new(user.clone()));
+    action(&mut season, &mut entry)?;
+    // An entry left without bets by cancellations is dropped
+    if entry.total_up + entry.total_down == 0 {
+        SEASON_ENTRIES.remove(storage, (season.id, user))?;
+    } else {
+        SEASON_ENTRIES.save(storage, (season.id, user), &entry)?;
+    }
+    SEASONS.save(storage, season.id, &season)
+}
+
+/// Removes a cancelled bet from the season of its round
+pub fn record_cancel(
+    storage: &mut dyn Storage,
+    round: &Round,
+    user: &Addr,
+    bet: &Bet,
+) -> StdResult<()> {
+    update_entry(storage, round, user, |season, entry| {
+        entry.remove_bet(bet);
+        season.open_bets -= 1;
+        Ok(())
+    })
+}
+
+/// Bets of a voided round are refunded, they never get an outcome
+pub fn record_void(storage: &mut dyn Storage, round: &Round) -> StdResult<()> {
+    let Some(mut season) = season_at(storage, round.start_time)? else {
+        return Ok(());
+    };
+
+    season.open_bets -= round.participant_count;
+    SEASONS.save(storage, season.id, &season)
+}
+
+/// Moves the season's share of a settled round's treasury fee to its prize pool and
+/// returns that share
+pub fn take_fee_share(
+    storage: &mut dyn Storage,
+    round: &Round,
+    fee: Uint128,
+) -> StdResult<Uint128> {
+    let Some(mut season) = season_at(storage, round.start_time)? else {
+        return Ok(Uint128::zero());
+    };
+
+    let share = payout::bps_share(fee, season.fee_share)?;
+    season.prize_pool += share;
+    SEASONS.save(storage, season.id, &season)?;
+
+    Ok(share)
+}
+
+pub fn execute_create_season(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    start_time: u64,
+    end_time: u64,
+    fee_share: u64,
+    payout_curve: Vec<u64>,
+) -> Result<Response, ContractError> {
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.owner {
+        return Err(ContractError::UnauthorizedOwner {});
+    }
+
+    // Rounds started before the season existed would have uncounted bets
+    let start_time = Timestamp::from_seconds(start_time);
+    let end_time = Timestamp::from_seconds(end_time);
+    let last: Option<Season> = SEASONS
+        .range(deps.storage, None, None, Order::Descending)
+        .next()
+        .transpose()?
+        .map(|(_, season)| season);
+    if start_time <= env.block.time
+        || end_time <= start_time
+        || last.as_ref().is_some_and(|last| start_time < last.end_time)
+    {
+        return Err(ContractError::InvalidSeasonTime {});
+    }
+    if fee_share > 10_000 {
+        return Err(ContractError::InvalidFeeShare {});
+    }
+    if payout_curve.is_empty() || payout_curve.iter().sum::<u64>() > 10_000 {
+        return Err(ContractError::InvalidPayoutCurve {});
+    }
+
+    let season = Season {
+        id: last.map_or(1, |last| last.id + 1),
+        start_time,
+        end_time,
+        fee_share,
+        payout_curve,
+        prize_pool: Uint128::zero(),
+        open_bets: 0,
+    };
+    SEASONS.save(deps.storage, season.id, &season)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "create_season")
+        .add_attribute("season_id", season.id.to_string())
+        .add_attribute("start_time", start_time.seconds().to_string())
+        .add_attribute("end_time", end_time.seconds().to_string())
+        .add_attribute("fee_share", fee_share.to_string()))
+}
+
+pub fn execute_fund_season(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    season_id: u64,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let amount = native_stake(&config, &info.funds)?;
+
+    fund_season(deps, env, info.sender, season_id, amount)
+}
+
+/// Adds `amount` of the stake asset, already received from `funder`, to a prize pool
+pub fn fund_season(
+    deps: DepsMut,
+    env: Env,
+    funder: Addr,
+    season_id: u64,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    let mut season: Season = SEASONS
+        .may_load(deps.storage, season_id)?
+        .ok_or(ContractError::SeasonNotFound {})?;
+    if env.block.time >= season.end_time {
+        return Err(ContractError::SeasonEnded {});
+    }
+    if amount.is_zero() {
+        return Err(ContractError::NoFundSent {});
+    }
+
+    season.prize_pool += amount;
+    SEASONS.save(deps.storage, season_id, &season)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "fund_season")
+        .add_attribute("season_id", season_id.to_string())
+        .add_attribute("funder", funder)
+        .add_attribute("amount", amount)
+        .add_attribute("prize_pool", season.prize_pool))
+}
+
+pub fn execute_claim_season_prize(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    season_id: u64,
+) -> Result<Response, ContractError> {
+    let config: Config = CONFIG.load(deps.storage)?;
+    let season: Season = SEASONS
+        .may_load(deps.storage, season_id)?
+        .ok_or(ContractError::SeasonNotFound {})?;
+    if !season.is_closed(env.block.time) {
+        return Err(ContractError::SeasonNotClosed {});
+    }
+    if SEASON_PRIZES.has(deps.storage, (season_id, &info.sender)) {
+        return Err(ContractError::AlreadyClaimed {});
+    }
+
+    // Rank by net profit, ties are ordered by address like the leaderboard
+    let entry: LeaderboardEntry = SEASON_ENTRIES
+        .may_load(deps.storage, (season_id, &info.sender))?
+        .filter(LeaderboardEntry::has_settled_bets)
+        .ok_or(ContractError::NoSeasonPrize {})?;
+    let key = (entry.net_profit.i128(), (season_id, info.sender.clone()));
+    let rank = count_ranked(deps.storage, &season, Some(key))?;
+    let prize = match season.payout_curve.get(rank) {
+        Some(share) => payout::bps_share(season.prize_pool, *share)?,
+        None => Uint128::zero(),
+    };
+    if prize.is_zero() {
+        return Err(ContractError::NoSeasonPrize {});
+    }
+    SEASON_PRIZES.save(deps.storage, (season_id, &info.sender), &prize)?;
+
+    let payout = config.stake_asset.transfer_msg(&info.sender, prize)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "claim_season_prize")
+        .add_attribute("season_id", season_id.to_string())
+        .add_attribute("user", info.sender.to_string())
+        .add_attribute("rank", (rank + 1).to_string())
+        .add_attribute("prize", prize)
+        .add_message(payout))
+}
+
+/// Moves the part of a closed season's prize pool that no rank can claim to the treasury:
+/// the shares of ranks nobody reached, what the payout curve leaves and rounding dust
+pub fn execute_sweep_season(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    season_id: u64,
+) -> Result<Response, ContractError> {
+    let roles: Roles = ROLES.load(deps.storage)?;
+    if info.sender != roles.owner {
+        return Err(ContractError::UnauthorizedOwner {});
+    }
+    let season: Season = SEASONS
+        .may_load(deps.storage, season_id)?
+        .ok_or(ContractError::SeasonNotFound {})?;
+    if !season.is_closed(env.block.time) {
+        return Err(ContractError::SeasonNotClosed {});
+    }
+    if SEASON_SWEPT.has(deps.storage, season_id) {
+        return Err(ContractError::SeasonAlreadySwept {});
+    }
+
+    let ranked = count_ranked(deps.storage, &season, None)?;
+    let mut payable = Uint128::zero();
+    for share in season.payout_curve.iter().take(ranked) {
+        payable = payable
+            .checked_add(payout::bps_share(season.prize_pool, *share)?)
+            .map_err(StdError::from)?;
+    }
+    let swept = season
+        .prize_pool
+        .checked_sub(payable)
+        .map_err(StdError::from)?;
+    SEASON_SWEPT.save(deps.storage, season_id, &swept)?;
+    TREASURY_BALANCE.update(deps.storage, |mut treasury| -> StdResult<_> {
+        treasury.accrued = treasury.accrued.checked_add(swept)?;
+        Ok(treasury)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "sweep_season")
+        .add_attribute("season_id", season_id.to_string())
+        .add_attribute("amount", swept))
+}
+
+/// Number of ranked entries of a season above the index key `above`, or in total without
+/// it, counted up to the number of paid ranks
+fn count_ranked(
+    storage: &dyn Storage,
+    season: &Season,
+    above: Option<(i128, (u64, Addr))>,
+) -> StdResult<usize> {
+    let entries: Vec<_> = SEASON_ENTRIES
+        .idx
+        .net_profit
+        .sub_prefix(season.id)
+        .range(storage, above.map(Bound::exclusive), None, Order::Ascending)
+        .filter(|item| {
+            item.as_ref()
+                .map_or(true, |(_, entry)| entry.has_settled_bets())
+        })
+        .take(season.payout_curve.len())
+        .collect::<StdResult<_>>()?;
+    Ok(entries.len())
+}
@@ -1,15 +1,176 @@
-use cosmwasm_std::{Addr, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map};
-use crate::msg::{Direction, LeaderboardEntry};
+use cosmwasm_std::{
+    to_json_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Int128, StdError, StdResult, Timestamp, Uint128,
+    WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, KeyDeserialize, Map, MultiIndex};
+use crate::payout::{bet_net_profit, bet_reward};
+use crate::msg::{Direction, LeaderboardEntry, RoundResponse, RoundStatus};
 use cosmwasm_schema::cw_serde;
 
 /// Configuration of the contract
 #[cw_serde]
 pub struct Config {
-    pub admin: Addr,
     pub paused: bool,
-    pub treasury_fee: u64, // e.g., 5 means 5%
-    pub current_round_id: u64
+    /// Asset accepted for bets and used for every payout
+    pub stake_asset: StakeAsset,
+    /// Where lock and end prices come from
+    pub oracle: OracleSource,
+    pub treasury_fee: u64, // basis points, e.g., 200 means 2%
+    /// Share of the treasury fee paid to whoever ends a round, in basis points
+    pub keeper_reward: u64,
+    /// Round currently open for betting
+    pub current_round_id: u64,
+    pub genesis_start_once: bool,
+    pub genesis_lock_once: bool,
+    /// Seconds a round accepts bets before it can be locked
+    pub betting_interval: u64,
+    /// Seconds between locking a round and ending it
+    pub live_interval: u64,
+    /// Grace period in seconds after lock/end time before the call is rejected
+    pub buffer_seconds: u64,
+    /// Smallest accepted bet or top-up
+    pub min_bet: Uint128,
+    /// Largest accepted bet or top-up
+    pub max_bet: Option<Uint128>,
+    /// Most a user can stake in one round, top-ups included
+    pub max_user_stake: Option<Uint128>,
+    /// Most that can be staked in one round across all users
+    pub max_round_pool: Option<Uint128>,
+    /// Share of the stake kept by the treasury when a bet is cancelled, in basis points
+    pub cancel_fee: u64,
+}
+
+impl Config {
+    /// Both intervals must be non-zero and the buffer shorter than either of them. One call
+    /// locks round N and ends round N-1, whose clocks start at the same block, so the
+    /// intervals must not differ by more than the buffer or the two windows never overlap.
+    pub fn has_valid_timing(&self) -> bool {
+        self.betting_interval > 0
+            && self.live_interval > 0
+            && self.buffer_seconds < self.betting_interval
+            && self.buffer_seconds < self.live_interval
+            && self.betting_interval.abs_diff(self.live_interval) <= self.buffer_seconds
+    }
+
+    /// The minimum bet must not be above the maximum bet or either cap, no bet could be
+    /// placed otherwise
+    pub fn has_valid_bet_limits(&self) -> bool {
+        [self.max_bet, self.max_user_stake, self.max_round_pool]
+            .iter()
+            .flatten()
+            .all(|limit| self.min_bet <= *limit)
+    }
+
+    /// Rounds can be progressed by anyone when the price does not come from the operator
+    pub fn is_permissionless(&self) -> bool {
+        !matches!(self.oracle, OracleSource::Manual)
+    }
+}
+
+/// Asset a market is staked in
+#[cw_serde]
+pub enum StakeAsset {
+    /// Bank denom, including token-factory and IBC denoms
+    Native { denom: String },
+    /// CW20 token, bets arrive through the `Receive` hook
+    Cw20 { contract_addr: Addr },
+}
+
+impl StakeAsset {
+    /// Message paying `amount` of the stake asset out of the contract to `recipient`
+    pub fn transfer_msg(&self, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+        let msg = match self {
+            StakeAsset::Native { denom } => BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin {
+                    denom: denom.clone(),
+                    amount,
+                }],
+            }
+            .into(),
+            StakeAsset::Cw20 { contract_addr } => WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }
+            .into(),
+        };
+
+        Ok(msg)
+    }
+}
+
+/// Source of the lock and end prices
+#[cw_serde]
+pub enum OracleSource {
+    /// The operator submits the price with every round call
+    Manual,
+    /// The price is queried from a contract implementing `oracle::OracleQueryMsg`
+    Feed {
+        contract_addr: Addr,
+        /// Maximum age of the published price in seconds
+        max_age: u64,
+        /// Maximum confidence interval as basis points of the price
+        max_confidence_bps: u64,
+    },
+    /// The price is relayed by anyone with signatures of `threshold` of the `signers`
+    Signed {
+        /// Public keys of the whitelisted price signers
+        signers: Vec<Binary>,
+        threshold: u32,
+        scheme: SignatureScheme,
+        /// Maximum age of the signed price in seconds
+        max_age: u64,
+    },
+}
+
+#[cw_serde]
+pub enum SignatureScheme {
+    Secp256k1,
+    Ed25519,
+}
+
+impl OracleSource {
+    /// Validates the feed address of a user supplied oracle source
+    pub fn validate(self, api: &dyn Api) -> StdResult<Self> {
+        match self {
+            OracleSource::Manual => Ok(OracleSource::Manual),
+            OracleSource::Feed {
+                contract_addr,
+                max_age,
+                max_confidence_bps,
+            } => Ok(OracleSource::Feed {
+                contract_addr: api.addr_validate(contract_addr.as_str())?,
+                max_age,
+                max_confidence_bps,
+            }),
+            OracleSource::Signed {
+                signers,
+                threshold,
+                scheme,
+                max_age,
+            } => {
+                if threshold == 0 || threshold as usize > signers.len() {
+                    return Err(StdError::generic_err(
+                        "Signer threshold must be between 1 and the number of signers",
+                    ));
+                }
+                if (1..signers.len()).any(|i| signers[..i].contains(&signers[i])) {
+                    return Err(StdError::generic_err("Duplicate price signer"));
+                }
+                Ok(OracleSource::Signed {
+                    signers,
+                    threshold,
+                    scheme,
+                    max_age,
+                })
+            }
+        }
+    }
 }
 
 /// Round definition
@@ -26,8 +187,110 @@ pub struct Round {
     pub lock_price: Uint128,
     pub end_price: Uint128,
     pub result: Option<Direction>,
-    pub executed: bool,
-    pub participants: Vec<Addr>
+    /// Pool of the winning side, set at settlement
+    pub reward_base: Uint128,
+    /// Total pool minus the treasury fee, shared by the winners
+    pub reward_amount: Uint128,
+    pub status: RoundStatus,
+    /// Number of bettors, the bets themselves are keyed by round in `USER_BETS`
+    pub participant_count: u64,
+}
+
+impl Round {
+    /// A round is ended once it has been settled, voided by a tie or cancelled
+    pub fn is_ended(&self) -> bool {
+        matches!(
+            self.status,
+            RoundStatus::Settled | RoundStatus::Refunding | RoundStatus::Cancelled
+        )
+    }
+
+    /// Bettors of a voided round get their stake back instead of a reward
+    pub fn is_voided(&self) -> bool {
+        matches!(self.status, RoundStatus::Refunding | RoundStatus::Cancelled)
+    }
+}
+
+impl From<Round> for RoundResponse {
+    fn from(round: Round) -> Self {
+        RoundResponse {
+            id: round.id,
+            bull_pool: round.bull_pool,
+            bear_pool: round.bear_pool,
+            total_pool: round.total_pool,
+            start_time: round.start_time.seconds(),
+            lock_time: round.lock_time.seconds(),
+            end_time: round.end_time.seconds(),
+            start_price: round.start_price,
+            lock_price: round.lock_price,
+            end_price: round.end_price,
+            result: round.result,
+            reward_base: round.reward_base,
+            reward_amount: round.reward_amount,
+            status: round.status,
+            participant_count: round.participant_count,
+        }
+    }
+}
+
+impl LeaderboardEntry {
+    pub fn new(user: Addr) -> Self {
+        LeaderboardEntry {
+            user,
+            total_amount_played: Uint128::zero(),
+            total_won: 0,
+            total_lost: 0,
+            total_up: 0,
+            total_down: 0,
+            amount_won: Uint128::zero(),
+            amount_lost: Uint128::zero(),
+            net_profit: Int128::zero(),
+        }
+    }
+
+    /// Only users with a settled bet are ranked for season prizes
+    pub fn has_settled_bets(&self) -> bool {
+        self.total_won + self.total_lost > 0
+    }
+
+    /// Counts a newly placed bet
+    pub fn record_bet(&mut self, bet: &Bet) {
+        self.total_amount_played += bet.amount;
+        match bet.direction {
+            Direction::Up => self.total_up += 1,
+            Direction::Down => self.total_down += 1,
+        }
+    }
+
+    /// Counts stake added to an existing bet
+    pub fn record_top_up(&mut self, amount: Uint128) {
+        self.total_amount_played += amount;
+    }
+
+    /// Reverses `record_bet` and any top-ups for a cancelled bet
+    pub fn remove_bet(&mut self, bet: &Bet) {
+        self.total_amount_played -= bet.amount;
+        match bet.direction {
+            Direction::Up => self.total_up -= 1,
+            Direction::Down => self.total_down -= 1,
+        }
+    }
+
+    /// Applies the outcome of a bet on a settled round
+    pub fn record_outcome(&mut self, round: &Round, bet: &Bet) -> StdResult<()> {
+        match bet_reward(round, bet)? {
+            Some(reward) => {
+                self.amount_won += reward;
+                self.total_won += 1;
+            }
+            None => {
+                self.amount_lost += bet.amount;
+                self.total_lost += 1;
+            }
+        }
+        self.net_profit += bet_net_profit(round, bet)?;
+        Ok(())
+    }
 }
 
 /// Bet placed by a user
@@ -35,6 +298,26 @@ pub struct Round {
 pub struct Bet {
     pub amount: Uint128,
     pub direction: Direction,
+    /// Set by ClaimReward or ClaimRefund. Rewards are paid out of the winnings ledger, so
+    /// a winning bet may also have been paid by WithdrawWinnings.
+    pub claimed: bool,
+    /// Set once the round's outcome has been applied to the winnings and leaderboard
+    pub settled: bool,
+}
+
+/// Treasury fees taken at settlement
+#[cw_serde]
+#[derive(Default)]
+pub struct TreasuryBalance {
+    pub accrued: Uint128,
+    pub withdrawn: Uint128,
+}
+
+impl TreasuryBalance {
+    /// Fees accrued but not yet claimed
+    pub fn available(&self) -> Uint128 {
+        self.accrued - self.withdrawn
+    }
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
@@ -46,10 +329,176 @@ pub const CONFIG: Item<Config> = Item::new("config");
 pub const ROUNDS: Map<u64, Round> = Map::new("rounds");
 
 /// Map from (round_id, user_address) => Bet
-pub const USER_BETS: Map<(u64, &Addr), Bet> = Map::new("user_bets");
+pub const USER_BETS: IndexedMap<(u64, &Addr), Bet, BetIndexes> = IndexedMap::new(
+    "user_bets",
+    BetIndexes {
+        user: MultiIndex::new(bet_user, "user_bets", "user_bets__user"),
+    },
+);
+
+/// Secondary indexes of `USER_BETS`
+pub struct BetIndexes<'a> {
+    /// Bets by bettor, ordered by round id
+    pub user: MultiIndex<'a, Addr, Bet, (u64, Addr)>,
+}
+
+impl IndexList<Bet> for BetIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Bet>> + '_> {
+        let v: Vec<&dyn Index<Bet>> = vec![&self.user];
+        Box::new(v.into_iter())
+    }
+}
+
+/// The bettor is part of the primary key, not of the bet itself
+fn bet_user(pk: &[u8], _bet: &Bet) -> Addr {
+    let (_, user) = <(u64, Addr)>::from_slice(pk).unwrap();
+    user
+}
 
-/// User winnings claimable (used in ClaimReward)
+/// Last bettor settled by ProcessSettlement, per round
+pub const SETTLEMENT_CURSOR: Map<u64, Addr> = Map::new("settlement_cursor");
+
+/// Rewards paid to the winners of a settled round so far, the rest of its reward amount
+/// goes to the treasury as dust once all of its bets are settled
+#[cw_serde]
+#[derive(Default)]
+pub struct RoundPayout {
+    pub paid: Uint128,
+    pub settled_bets: u64,
+}
+
+/// Only kept for settled rounds with bets left to settle
+pub const ROUND_PAYOUTS: Map<u64, RoundPayout> = Map::new("round_payouts");
+
+/// User winnings claimable, credited when a bet is settled and debited by ClaimReward/WithdrawWinnings
 pub const WINNINGS: Map<&Addr, Uint128> = Map::new("winnings");
 
+/// Nonce of the last accepted signed price
+pub const LAST_PRICE_NONCE: Item<u64> = Item::new("last_price_nonce");
+
+/// Addresses allowed to manage the contract, each role is checked separately
+#[cw_serde]
+pub struct Roles {
+    /// Updates the config and the other roles
+    pub owner: Addr,
+    /// Runs the round pipeline and cancels rounds
+    pub operator: Addr,
+    /// Pauses and resumes the contract
+    pub pauser: Addr,
+    /// Receives the fees withdrawn with ClaimTreasury
+    pub treasury: Addr,
+}
+
+/// Ownership offered to `owner` until it is accepted or `expires_at` passes
+#[cw_serde]
+pub struct PendingOwner {
+    pub owner: Addr,
+    pub expires_at: Timestamp,
+}
+
+pub const ROLES: Item<Roles> = Item::new("roles");
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new("pending_owner");
+
+/// Fees accrued and withdrawn by the treasury
+pub const TREASURY_BALANCE: Item<TreasuryBalance> = Item::new("treasury_balance");
+
 /// Leaderboard stat (total won per user)
-pub const LEADERBOARDENTRY: Map<&Addr, LeaderboardEntry> = Map::new("leaderboard");
+pub const LEADERBOARDENTRY: IndexedMap<&Addr, LeaderboardEntry, LeaderboardIndexes> =
+    IndexedMap::new(
+        "leaderboard",
+        LeaderboardIndexes {
+            net_profit: MultiIndex::new(net_profit_key, "leaderboard", "leaderboard__net_profit"),
+            amount_won: MultiIndex::new(amount_won_key, "leaderboard", "leaderboard__amount_won"),
+            win_count: MultiIndex::new(win_count_key, "leaderboard", "leaderboard__win_count"),
+            volume: MultiIndex::new(volume_key, "leaderboard", "leaderboard__volume"),
+        },
+    );
+
+/// Ranking indexes of `LEADERBOARDENTRY`, one per `LeaderboardSort`
+pub struct LeaderboardIndexes<'a> {
+    pub net_profit: MultiIndex<'a, i128, LeaderboardEntry, Addr>,
+    pub amount_won: MultiIndex<'a, u128, LeaderboardEntry, Addr>,
+    pub win_count: MultiIndex<'a, u64, LeaderboardEntry, Addr>,
+    pub volume: MultiIndex<'a, u128, LeaderboardEntry, Addr>,
+}
+
+impl IndexList<LeaderboardEntry> for LeaderboardIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<LeaderboardEntry>> + '_> {
+        let v: Vec<&dyn Index<LeaderboardEntry>> =
+            vec![&self.net_profit, &self.amount_won, &self.win_count, &self.volume];
+        Box::new(v.into_iter())
+    }
+}
+
+fn net_profit_key(_pk: &[u8], entry: &LeaderboardEntry) -> i128 {
+    entry.net_profit.i128()
+}
+
+fn amount_won_key(_pk: &[u8], entry: &LeaderboardEntry) -> u128 {
+    entry.amount_won.u128()
+}
+
+fn win_count_key(_pk: &[u8], entry: &LeaderboardEntry) -> u64 {
+    entry.total_won
+}
+
+fn volume_key(_pk: &[u8], entry: &LeaderboardEntry) -> u128 {
+    entry.total_amount_played.u128()
+}
+
+/// Competition over the bets of the rounds started between `start_time` and `end_time`
+#[cw_serde]
+pub struct Season {
+    pub id: u64,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+    /// Share of the treasury fee of the season's rounds added to the prize pool, in basis points
+    pub fee_share: u64,
+    /// Prize of each rank from the first, in basis points of the prize pool
+    pub payout_curve: Vec<u64>,
+    pub prize_pool: Uint128,
+    /// Bets of the season's rounds whose outcome is not in the season entries yet
+    pub open_bets: u64,
+}
+
+impl Season {
+    /// Rankings are final once the season is over and every bet of its rounds is settled
+    pub fn is_closed(&self, now: Timestamp) -> bool {
+        now >= self.end_time && self.open_bets == 0
+    }
+}
+
+/// Seasons by id, they never overlap so ids follow start times
+pub const SEASONS: Map<u64, Season> = Map::new("seasons");
+
+/// Map from (season_id, user_address) => the user's stats over the season
+pub const SEASON_ENTRIES: IndexedMap<(u64, &Addr), LeaderboardEntry, SeasonEntryIndexes> =
+    IndexedMap::new(
+        "season_entries",
+        SeasonEntryIndexes {
+            net_profit: MultiIndex::new(season_net_profit_key, "season_entries", "season_entries__net_profit"),
+        },
+    );
+
+/// Ranking index of `SEASON_ENTRIES`, seasons are ranked by net profit
+pub struct SeasonEntryIndexes<'a> {
+    pub net_profit: MultiIndex<'a, (u64, i128), LeaderboardEntry, (u64, Addr)>,
+}
+
+impl IndexList<LeaderboardEntry> for SeasonEntryIndexes<'_> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<LeaderboardEntry>> + '_> {
+        let v: Vec<&dyn Index<LeaderboardEntry>> = vec![&self.net_profit];
+        Box::new(v.into_iter())
+    }
+}
+
+fn season_net_profit_key(pk: &[u8], entry: &LeaderboardEntry) -> (u64, i128) {
+    let (season_id, _) = <(u64, Addr)>::from_slice(pk).unwrap();
+    (season_id, entry.net_profit.i128())
+}
+
+/// Prize paid to each winner of a season
+pub const SEASON_PRIZES: Map<(u64, &Addr), Uint128> = Map::new("season_prizes");
+
+/// Part of a closed season's prize pool no rank could claim, moved to the treasury
+pub const SEASON_SWEPT: Map<u64, Uint128> = Map::new("season_swept");